@@ -1,11 +1,20 @@
+/// A pair of delimiters enclosing a block comment, which may span several lines
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Block<R> {
+    /// The delimiter opening the comment, i.e. `/*`
+    pub start: R,
+    /// The delimiter closing the comment, i.e. `*/`
+    pub end: R,
+    /// Whether the delimiters only count at the start of a line, as with Perl's `=pod` and `=cut`
+    pub line_start: bool,
+}
+
 /// Defines comment styles for any language
 pub trait Comment {
     /// The type of the comment (`String`, `&'static str`, etc.)
     type Rep;
     /// Returns the single line comment style, if any
     fn single(&self) -> Option<Vec<<Self as Comment>::Rep>>;
-    /// Returns the start of a multi-line comment style, if any
-    fn multi_start(&self) -> Option<<Self as Comment>::Rep>;
-    /// Returns the end of a multi-line comment style, if any
-    fn multi_end(&self) -> Option<<Self as Comment>::Rep>;
+    /// Returns every block comment style, which is empty if there are none
+    fn multi(&self) -> Vec<Block<<Self as Comment>::Rep>>;
 }
@@ -1,83 +1,427 @@
 
 
-use clap::ArgMatches;
-
 use error::{CliError, CliResult};
+use langdefs::LanguageTable;
 use language::Language;
 use std::env;
-use std::path::{Path, PathBuf};
-
-arg_enum! {
-    #[derive(Debug)]
-    pub enum Utf8Rule {
-        Ignore,
-        Lossy,
-        Strict
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// How files which aren't valid UTF-8 are handled
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Utf8Rule {
+    /// Skip them
+    Ignore,
+    /// Replace any invalid sequences with U+FFFD before counting them
+    Lossy,
+    /// Stop with an error
+    Strict,
+}
+
+impl FromStr for Utf8Rule {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &*s.to_lowercase() {
+            "ignore" => Ok(Utf8Rule::Ignore),
+            "lossy" => Ok(Utf8Rule::Lossy),
+            "strict" => Ok(Utf8Rule::Strict),
+            _ => Err("valid values: ignore, lossy, strict".to_owned()),
+        }
+    }
+}
+
+/// The value the rows of the results are sorted by
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SortBy {
+    /// All lines, the most first
+    Lines,
+    /// Lines of code, the most first
+    Code,
+    /// Lines of comments, the most first
+    Comments,
+    /// Blank lines, the most first
+    Blanks,
+    /// Files, the most first
+    Files,
+    /// Unsafe lines, the most first
+    Unsafe,
+    /// The name of each row, alphabetically
+    Name,
+}
+
+impl FromStr for SortBy {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &*s.to_lowercase() {
+            "lines" => Ok(SortBy::Lines),
+            "code" => Ok(SortBy::Code),
+            "comments" => Ok(SortBy::Comments),
+            "blanks" => Ok(SortBy::Blanks),
+            "files" => Ok(SortBy::Files),
+            "unsafe" => Ok(SortBy::Unsafe),
+            "name" => Ok(SortBy::Name),
+            _ => Err("valid values: lines, code, comments, blanks, files, unsafe, name".to_owned()),
+        }
+    }
+}
+
+/// What each row of the results totals the files of
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum GroupBy {
+    /// Each language, including the code embedded in files of other languages
+    Language,
+    /// Each directory directly holding files
+    Dir,
+    /// Each crate, i.e. the nearest directory above a file with a `Cargo.toml` for a package
+    Crate,
+    /// Each target of a crate, such as its library, binaries, tests and examples
+    Target,
+    /// Each file extension
+    Extension,
+}
+
+impl GroupBy {
+    /// The heading of the column naming each group
+    pub fn heading(&self) -> &'static str {
+        match *self {
+            GroupBy::Language => "Language",
+            GroupBy::Dir => "Directory",
+            GroupBy::Crate => "Crate",
+            GroupBy::Target => "Target",
+            GroupBy::Extension => "Extension",
+        }
     }
 }
 
+impl FromStr for GroupBy {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &*s.to_lowercase() {
+            "language" => Ok(GroupBy::Language),
+            "dir" => Ok(GroupBy::Dir),
+            "crate" => Ok(GroupBy::Crate),
+            "target" => Ok(GroupBy::Target),
+            "extension" => Ok(GroupBy::Extension),
+            _ => Err("valid values: language, dir, crate, target, extension".to_owned()),
+        }
+    }
+}
+
+/// A value derived from the totals of each row of the results
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Metric {
+    /// The lines of comments for each line of code, and the fraction of lines which are blank
+    Ratio,
+    /// The average lines in each file
+    Avg,
+    /// The unsafe lines in each thousand lines of code
+    Density,
+}
+
+impl FromStr for Metric {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &*s.to_lowercase() {
+            "ratio" => Ok(Metric::Ratio),
+            "avg" => Ok(Metric::Avg),
+            "density" => Ok(Metric::Density),
+            _ => Err("valid values: ratio, avg, density".to_owned()),
+        }
+    }
+}
+
+/// The format the results are written in
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum OutputFormat {
+    /// A table for people
+    Text,
+    /// A single JSON object for other tools
+    Json,
+    /// GitHub-flavored Markdown tables, i.e. for pull request comments
+    Markdown,
+    /// A self-contained HTML page with sortable tables and a bar chart of each row
+    Html,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &*s.to_lowercase() {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            "markdown" => Ok(OutputFormat::Markdown),
+            "html" => Ok(OutputFormat::Html),
+            _ => Err("valid values: text, json, markdown, html".to_owned()),
+        }
+    }
+}
+
+/// What to count, and how to count and display it, which is built with a `ConfigBuilder`
 #[derive(Debug)]
 pub struct Config<'a> {
-    pub verbose: bool,
-    pub all: bool,
-    pub thousands: Option<char>,
-    pub utf8_rule: Utf8Rule,
-    pub usafe: bool,
-    pub exclude: Vec<PathBuf>,
-    pub exts: Option<Vec<&'a str>>,
-    pub to_count: Vec<PathBuf>,
-    pub follow_links: bool,
-}
-
-impl<'a> Config<'a> {
-    pub fn from_matches(m: &'a ArgMatches<'a>) -> CliResult<Self> {
-        if let Some(ext_vec) = m.values_of("language") {
-            for e in ext_vec {
-                if let None = Language::from_ext(e) {
+    /// Print what's being done along the way
+    pub(crate) verbose: bool,
+    /// Count files ignored by the `.gitignore` of the current directory
+    pub(crate) all: bool,
+    /// The thousands separator used when displaying numbers
+    pub(crate) thousands: Option<char>,
+    /// How files which aren't valid UTF-8 are handled
+    pub(crate) utf8_rule: Utf8Rule,
+    /// Count the unsafe lines of each language
+    pub(crate) usafe: bool,
+    /// The files and directories not to count
+    pub(crate) exclude: Vec<PathBuf>,
+    /// Only count these languages, by their extensions
+    pub(crate) exts: Option<Vec<&'a str>>,
+    /// The files, directories and globs to count
+    pub(crate) to_count: Vec<PathBuf>,
+    /// Count the files listed in this file, or stdin if it's `-`, instead of searching
+    /// `to_count`. The paths may be separated by newlines or NULs.
+    pub(crate) files_from: Option<&'a str>,
+    /// Count stdin as a single file in this language, instead of any files
+    pub(crate) stdin: Option<Language>,
+    /// Display how each line of this file is classified, instead of counting
+    pub(crate) explain: Option<PathBuf>,
+    /// Follow symlinks
+    pub(crate) follow_links: bool,
+    /// Display the size of the lines in bytes and characters, with the average and longest line
+    pub(crate) sizes: bool,
+    /// Report unsafe blocks without a comment containing `safety_marker` before them
+    pub(crate) check_safety: bool,
+    /// The text which justifies an unsafe block when using `check_safety`
+    pub(crate) safety_marker: &'a str,
+    /// Count each dependency in `Cargo.lock` rather than `to_count`
+    pub(crate) deps: bool,
+    /// Display the dependencies as a tree, with the totals of each crate's own dependencies
+    pub(crate) deps_tree: bool,
+    /// The format the results are written in
+    pub(crate) output: OutputFormat,
+    /// The built-in and user-defined languages
+    pub(crate) langs: LanguageTable,
+    /// Display the totals of each directory, down to this many levels below the one holding all
+    /// of the files counted
+    pub(crate) by_dir: Option<usize>,
+    /// Sort the rows of the results, rather than listing them in the order they were found
+    pub(crate) sort: Option<SortBy>,
+    /// Reverse the order the rows are sorted in
+    pub(crate) reverse: bool,
+    /// What each row of the results totals
+    pub(crate) group_by: GroupBy,
+    /// The values derived from the totals of each row to display, in order
+    pub(crate) metrics: Vec<Metric>,
+    /// Summarize the files skipped because their language wasn't recognized
+    pub(crate) skipped: bool,
+    /// List each of the skipped files
+    pub(crate) list_skipped: bool,
+    /// Display the totals of each file
+    pub(crate) per_file: bool,
+    /// Display the code embedded in other files, such as the `<script>` blocks of HTML files, as
+    /// rows beneath those files rather than adding it to the rows of its own language
+    pub(crate) embedded: bool,
+}
+
+impl<'a> Default for Config<'a> {
+    /// Counts the current directory using only the built-in languages, without unsafe statistics
+    fn default() -> Self {
+        let cwd = env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+        Config {
+            verbose: false,
+            all: false,
+            thousands: None,
+            utf8_rule: Utf8Rule::Strict,
+            usafe: false,
+            exclude: vec![cwd.join(".git")],
+            exts: None,
+            to_count: vec![cwd],
+            files_from: None,
+            stdin: None,
+            explain: None,
+            follow_links: false,
+            sizes: false,
+            check_safety: false,
+            safety_marker: "SAFETY:",
+            deps: false,
+            deps_tree: false,
+            output: OutputFormat::Text,
+            langs: LanguageTable::default(),
+            by_dir: None,
+            sort: None,
+            reverse: false,
+            group_by: GroupBy::Language,
+            metrics: vec![],
+            skipped: false,
+            list_skipped: false,
+            per_file: false,
+            embedded: false,
+        }
+    }
+}
+
+// Setters of the builder which store their value in the `Config` unchanged
+macro_rules! setters {
+    ($($(#[$doc:meta])* fn $name:ident($t:ty);)*) => {
+        $(
+            $(#[$doc])*
+            pub fn $name(mut self, $name: $t) -> Self {
+                self.cfg.$name = $name;
+                self
+            }
+        )*
+    };
+}
+
+/// Builds a `Config`, checking its options against the languages known when it's built
+///
+/// Options which aren't set keep the values of `Config::default()`, so only the built-in
+/// languages are known unless a `LanguageTable` is given with `langs`.
+#[derive(Debug)]
+pub struct ConfigBuilder<'a> {
+    cfg: Config<'a>,
+    // The language of stdin, by extension or name
+    stdin: Option<&'a str>,
+}
+
+impl<'a> Default for ConfigBuilder<'a> {
+    fn default() -> Self {
+        ConfigBuilder::new()
+    }
+}
+
+impl<'a> ConfigBuilder<'a> {
+    /// A builder counting the current directory, without unsafe statistics
+    pub fn new() -> Self {
+        ConfigBuilder {
+            cfg: Config {
+                exclude: vec![],
+                to_count: vec![],
+                ..Config::default()
+            },
+            stdin: None,
+        }
+    }
+
+    setters! {
+        /// Print what's being done along the way
+        fn verbose(bool);
+        /// Count files ignored by the `.gitignore` of the current directory
+        fn all(bool);
+        /// The thousands separator used when displaying numbers
+        fn thousands(Option<char>);
+        /// How files which aren't valid UTF-8 are handled
+        fn utf8_rule(Utf8Rule);
+        /// Count the unsafe lines of each language, which counting dependencies or their density
+        /// also does
+        fn usafe(bool);
+        /// The files and directories not to count, relative to the current directory unless
+        /// they're absolute. The current directory's `.git` is always excluded.
+        fn exclude(Vec<PathBuf>);
+        /// Only count the languages of these extensions
+        fn exts(Option<Vec<&'a str>>);
+        /// The files, directories and globs to count, or the current directory if there are none
+        fn to_count(Vec<PathBuf>);
+        /// Count the files listed in this file, or stdin if it's `-`, instead of searching
+        /// `to_count`. The paths may be separated by newlines or NULs.
+        fn files_from(Option<&'a str>);
+        /// Display how each line of this file is classified, instead of counting
+        fn explain(Option<PathBuf>);
+        /// Follow symlinks
+        fn follow_links(bool);
+        /// Display the size of the lines in bytes and characters, with the average and longest
+        /// line
+        fn sizes(bool);
+        /// Report unsafe blocks without a comment containing `safety_marker` before them
+        fn check_safety(bool);
+        /// The text which justifies an unsafe block when using `check_safety`
+        fn safety_marker(&'a str);
+        /// Count each dependency in `Cargo.lock` rather than `to_count`
+        fn deps(bool);
+        /// Display the dependencies as a tree, with the totals of each crate's own dependencies,
+        /// which implies `deps`
+        fn deps_tree(bool);
+        /// The format the results are written in
+        fn output(OutputFormat);
+        /// The built-in and user-defined languages
+        fn langs(LanguageTable);
+        /// Display the totals of each directory, down to this many levels below the one holding
+        /// all of the files counted
+        fn by_dir(Option<usize>);
+        /// Sort the rows of the results, rather than listing them in the order they were found
+        fn sort(Option<SortBy>);
+        /// Reverse the order the rows are sorted in
+        fn reverse(bool);
+        /// What each row of the results totals
+        fn group_by(GroupBy);
+        /// The values derived from the totals of each row to display, in order
+        fn metrics(Vec<Metric>);
+        /// Summarize the files skipped because their language wasn't recognized
+        fn skipped(bool);
+        /// List each of the skipped files, which implies `skipped`
+        fn list_skipped(bool);
+        /// Display the totals of each file
+        fn per_file(bool);
+        /// Display the code embedded in other files, such as the `<script>` blocks of HTML
+        /// files, as rows beneath those files rather than adding it to the rows of its own
+        /// language
+        fn embedded(bool);
+    }
+
+    /// Count stdin as a single file in the language named by `tag`, an extension or name such
+    /// as `rs` or `rust`, instead of any files
+    pub fn stdin(mut self, tag: Option<&'a str>) -> Self {
+        self.stdin = tag;
+        self
+    }
+
+    /// The `Config`, or an error if an extension or the language of stdin isn't known, or the
+    /// dependencies are to be written as Markdown or HTML
+    pub fn build(self) -> CliResult<Config<'a>> {
+        let mut cfg = self.cfg;
+        if (cfg.deps || cfg.deps_tree) &&
+           (cfg.output == OutputFormat::Markdown || cfg.output == OutputFormat::Html) {
+            return Err(CliError::Generic("the dependencies can only be written as text or JSON"
+                .to_owned()));
+        }
+        if (cfg.deps || cfg.deps_tree) && cfg.check_safety {
+            return Err(CliError::Generic("the safety of the dependencies can't be checked"
+                .to_owned()));
+        }
+        if let Some(ref exts) = cfg.exts {
+            for e in exts {
+                if let None = cfg.langs.from_ext(e) {
                     return Err(CliError::UnknownExt(format!("unsupported source code extension \
                                                              '{}'",
-                                                            e.to_owned())));
+                                                            e)));
                 }
             }
         }
-        Ok(Config {
-            verbose: m.is_present("verbose"),
-            all: m.is_present("all"),
-            thousands: m.value_of("separator").map(|s| s.chars().nth(0).unwrap()),
-            usafe: m.is_present("unsafe-statistics"),
-            utf8_rule: value_t!(m.value_of("utf8-rule"), Utf8Rule).unwrap_or(Utf8Rule::Strict),
-            exclude: if let Some(v) = m.values_of("exclude") {
-                debugln!("There are some");
-                let mut ret = vec![];
-                for p in v {
-                    let pb = Path::new(p);
-                    if pb.is_relative() {
-                        ret.push(cli_try!(env::current_dir()).join(p));
-                    } else {
-                        ret.push(pb.to_path_buf());
-                    }
-                }
-                debugln!("found files or dirs: {:?}", ret);
-                ret.push(cli_try!(env::current_dir()).join(".git"));
-                ret
-            } else {
-                debugln!("There aren't any, adding .git");
-                vec![cli_try!(env::current_dir()).join(".git")]
-            },
-            to_count: if let Some(v) = m.values_of("PATH") {
-                debugln!("There are some");
-                let mut ret = vec![];
-                for p in v {
-                    ret.push(PathBuf::from(p));
+        if let Some(tag) = self.stdin {
+            if cfg.deps || cfg.deps_tree || cfg.by_dir.is_some() {
+                return Err(CliError::Generic("stdin can't be counted with the dependencies or by \
+                                              directory"
+                    .to_owned()));
+            }
+            match cfg.langs.from_tag(tag) {
+                Some(lang) => cfg.stdin = Some(lang),
+                None => {
+                    return Err(CliError::UnknownExt(format!("unsupported language '{}'", tag)))
                 }
-                debugln!("found files or dirs: {:?}", ret);
-                ret
-            } else {
-                debugln!("There aren't any, using cwd");
-                vec![cli_try!(env::current_dir())]
-            },
-            exts: m.values_of("language").map(|v| v.collect()),
-            follow_links: m.is_present("follow-symlinks"),
-        })
+            }
+        }
+        let cwd = cli_try!(env::current_dir());
+        for p in &mut cfg.exclude {
+            if p.is_relative() {
+                *p = cwd.join(&p);
+            }
+        }
+        debugln!("found files or dirs to exclude: {:?}", cfg.exclude);
+        cfg.exclude.push(cwd.join(".git"));
+        if cfg.to_count.is_empty() {
+            debugln!("There aren't any, using cwd");
+            cfg.to_count.push(cwd);
+        }
+        cfg.deps = cfg.deps || cfg.deps_tree;
+        cfg.usafe = cfg.usafe || cfg.deps || cfg.metrics.contains(&Metric::Density);
+        cfg.skipped = cfg.skipped || cfg.list_skipped;
+        Ok(cfg)
     }
 }
@@ -1,22 +1,24 @@
 
 
-use comment::Comment;
 use config::{Config, Utf8Rule};
-use count::Count;
+use count::{Count, Dir, FileCount, Group, SourceCount, UnsafePolicy};
+use count::{group, report};
+use count::line::{Line, LineCounter, LineKind, UnsafeRes};
 use error::{CliError, CliResult};
-use fmt::{self, Format};
+use fmt::Format;
 use fsutil;
 use gitignore;
-use language::Language;
-use regex::Regex;
+use language::{embedded, Language};
+use std::cmp;
 use std::env;
-use std::f64;
-use std::fs::File;
-use std::io::{self, Read, Write};
-use std::path::{Path, PathBuf};
-
-use tabwriter::TabWriter;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::mem;
+use std::io::{self, Read};
+use std::path::PathBuf;
 
+/// The counts of each language found at the paths of a `Config`
+#[derive(Debug)]
 pub struct Counts<'c> {
     cfg: &'c Config<'c>,
     counts: Vec<Count>,
@@ -26,9 +28,15 @@ pub struct Counts<'c> {
     tot_blanks: u64,
     tot_code: u64,
     tot_usafe: u64,
+    tot_bytes: u64,
+    tot_chars: u64,
+    tot_longest: u64,
+    unjustified: Vec<(PathBuf, u64)>,
+    skipped: Vec<(PathBuf, u64)>,
 }
 
 impl<'c> Counts<'c> {
+    /// An empty set of counts for `cfg`
     pub fn new(cfg: &'c Config) -> Self {
         Counts {
             cfg: cfg,
@@ -39,11 +47,24 @@ impl<'c> Counts<'c> {
             tot_blanks: 0,
             tot_code: 0,
             tot_usafe: 0,
+            tot_bytes: 0,
+            tot_chars: 0,
+            tot_longest: 0,
+            unjustified: vec![],
+            skipped: vec![],
         }
     }
 
-    pub fn fill_from(&mut self) {
+    /// Adds all source files found at each of the paths to count which are not excluded, those
+    /// listed in `files_from` if it's set, or none if `stdin` is set
+    pub fn fill_from(&mut self) -> CliResult<()> {
         debugln!("executing; fill_from; cfg={:?}", self.cfg);
+        if let Some(list) = self.cfg.files_from {
+            return self.fill_from_list(list);
+        }
+        if self.cfg.stdin.is_some() {
+            return Ok(());
+        }
         let cd;
         let gitignore = if self.cfg.all {
             None
@@ -51,299 +72,456 @@ impl<'c> Counts<'c> {
             cd = env::current_dir().unwrap().join(".gitignore");
             gitignore::File::new(&cd).ok()
         };
-        for path in &self.cfg.to_count {
-            debugln!("iter; path={:?};", path);
-            let mut files = vec![];
-            fsutil::get_all_files(&mut files,
-                                  path,
-                                  &self.cfg.exclude,
-                                  self.cfg.follow_links,
-                                  &gitignore);
+        let cfg = self.cfg;
+        for path in &cfg.to_count {
+            self.fill_from_path(path, &gitignore);
+        }
+        Ok(())
+    }
 
-            for file in files {
-                debugln!("iter; file={:?};", file);
-                let extension = match Path::new(&file).extension() {
-                    Some(result) => {
-                        if let Some(ref exts) = self.cfg.exts {
-                            if !exts.contains(&result.to_str().unwrap_or("")) {
-                                continue;
-                            }
-                        }
-                        result.to_str().unwrap()
-                    }
-                    None => continue,
-                };
+    /// Adds the files listed in `list`, or read from stdin if it's `-`, one per line or
+    /// separated by NULs as with `git ls-files -z` and `find -print0`. Unlike the paths to count,
+    /// nothing is excluded and directories aren't searched.
+    pub fn fill_from_list(&mut self, list: &str) -> CliResult<()> {
+        debugln!("executing; fill_from_list; list={:?};", list);
+        // Paths aren't necessarily UTF-8, so they're read as bytes
+        let mut buf = vec![];
+        if list == "-" {
+            cli_try!(io::stdin().read_to_end(&mut buf));
+        } else {
+            cli_try!(cli_try!(File::open(list)).read_to_end(&mut buf));
+        }
+        let sep = if buf.contains(&b'\0') { b'\0' } else { b'\n' };
+        for mut path in buf.split(|&b| b == sep) {
+            while path.last() == Some(&b'\r') {
+                path = &path[..path.len() - 1];
+            }
+            if path.is_empty() {
+                continue;
+            }
+            let file = fsutil::path_from_bytes(path);
+            if !file.is_file() {
+                wlnerr!("{} {} isn't a file", Format::Warning("Skipping:"), file.display());
+                continue;
+            }
+            self.add_file(file);
+        }
+        Ok(())
+    }
 
-                debugln!("found extension: {:?}", extension);
-                if let Some(pos_lang) = Language::from_ext(extension) {
-                    debugln!("Extension is valid");
-                    let mut found = false;
-                    debugln!("Searching for previous entries of that type");
-                    for l in self.counts.iter_mut() {
-                        if l.lang == pos_lang {
-                            debugln!("Found");
-                            found = true;
-                            l.add_file(PathBuf::from(&file));
-                            break;
-                        }
-                    }
-                    if !found {
-                        debugln!("Not found, creating new");
-                        let mut c = Count::new(pos_lang, self.cfg.thousands);
-                        c.add_file(PathBuf::from(&file));
-                        self.counts.push(c);
-                    }
-                } else {
-                    debugln!("extension wasn't valid");
+    /// Adds all source files found at `path` (a file, directory, or glob) which are not excluded
+    pub fn fill_from_path(&mut self, path: &PathBuf, gitignore: &Option<gitignore::File>) {
+        debugln!("executing; fill_from_path; path={:?};", path);
+        let mut files = vec![];
+        fsutil::get_all_files(&mut files,
+                              path,
+                              &self.cfg.exclude,
+                              self.cfg.follow_links,
+                              gitignore);
+
+        for file in files {
+            self.add_file(file);
+        }
+    }
+
+    // Adds `file` to the count of its language, unless it's filtered out by `exts`, or records it
+    // as skipped if its language is unknown
+    fn add_file(&mut self, file: PathBuf) {
+        debugln!("iter; file={:?};", file);
+        let lang = self.cfg.langs.detect(&file);
+        if let Some(ref exts) = self.cfg.exts {
+            // Filter by language rather than the literal extension, so files detected by
+            // name or shebang are included as well
+            if lang.is_none() || !exts.iter().any(|e| self.cfg.langs.from_ext(e) == lang) {
+                return;
+            }
+        }
+
+        if let Some(pos_lang) = lang {
+            debugln!("Language is known; lang={:?}", pos_lang);
+            let mut found = false;
+            debugln!("Searching for previous entries of that type");
+            for l in self.counts.iter_mut() {
+                if l.lang == pos_lang {
+                    debugln!("Found");
+                    found = true;
+                    l.add_file(PathBuf::from(&file));
+                    break;
                 }
             }
+            if !found {
+                debugln!("Not found, creating new");
+                let mut c = Count::new(pos_lang, self.cfg.thousands);
+                c.add_file(PathBuf::from(&file));
+                self.counts.push(c);
+            }
+        } else {
+            debugln!("Language is unknown");
+            let size = fs::metadata(&file).map(|m| m.len()).unwrap_or(0);
+            self.skipped.push((file, size));
         }
     }
 
-    #[cfg_attr(feature = "lints", allow(cyclomatic_complexity, trivial_regex))]
+    /// Counts the lines of every file added so far, or the source read from stdin if `stdin` is
+    /// set
     pub fn count(&mut self) -> CliResult<()> {
+        let cfg = self.cfg;
         for count in self.counts.iter_mut() {
             debugln!("iter; count={:?};", count);
-            let re = if let Some(kw) = count.lang.unsafe_keyword() {
-                Regex::new(&*format!("(.*?)([:^word:]{}[:^word:])(.*)", kw)).unwrap()
-            } else {
-                Regex::new("").unwrap()
-            };
-            for file in count.files.iter() {
+            let res = UnsafeRes::new(&count.lang);
+            for file in count.files.clone() {
                 debugln!("iter; file={:?};", file);
-                let mut buffer = String::new();
+                let file_ref = cli_try!(File::open(&file));
+                if let Some(buffer) = try!(read_source(cfg.utf8_rule, file_ref)) {
+                    Counts::count_buffer(cfg,
+                                         count,
+                                         &res,
+                                         &file,
+                                         &buffer,
+                                         &mut self.unjustified,
+                                         None);
+                }
+            }
+        }
+        if let Some(ref lang) = cfg.stdin {
+            let count = try!(self.count_stdin(lang));
+            self.counts.push(count);
+        }
+        let counts = mem::replace(&mut self.counts, vec![]);
+        for count in &counts {
+            self.tot += count.files.len();
+            for c in Some(count).into_iter().chain(count.embedded.iter()) {
+                self.tot_lines += c.lines;
+                self.tot_comments += c.comments;
+                self.tot_blanks += c.blanks;
+                self.tot_code += c.code;
+                self.tot_usafe += c.usafe;
+                self.tot_bytes += c.bytes;
+                self.tot_chars += c.chars;
+                self.tot_longest = cmp::max(self.tot_longest, c.longest_line);
+            }
+        }
+        self.counts = counts;
+        if !cfg.embedded {
+            self.merge_embedded();
+        }
+        if let Some(sort) = cfg.sort {
+            group::sort(&mut self.counts, sort, cfg.reverse, group::count_key);
+            for count in self.counts.iter_mut() {
+                group::sort(&mut count.embedded, sort, cfg.reverse, group::count_key);
+            }
+        }
 
-                let mut file_ref = cli_try!(File::open(&file));
+        Ok(())
+    }
+
+    // Counts the source read from stdin in `lang`, as the file `-`
+    fn count_stdin(&mut self, lang: &Language) -> CliResult<Count> {
+        debugln!("counting stdin; lang={:?}", lang);
+        let cfg = self.cfg;
+        let file = PathBuf::from("-");
+        let mut count = Count::new(lang.clone(), cfg.thousands);
+        count.add_file(file.clone());
+        if let Some(buffer) = try!(read_source(cfg.utf8_rule, io::stdin())) {
+            Counts::count_buffer(cfg,
+                                 &mut count,
+                                 &UnsafeRes::new(lang),
+                                 &file,
+                                 &buffer,
+                                 &mut self.unjustified,
+                                 None);
+        }
+        Ok(count)
+    }
 
-                match self.cfg.utf8_rule {
-                    Utf8Rule::Ignore => {
-                        if let Err(..) = file_ref.read_to_string(&mut buffer) {
-                            continue;
-                        }
-                    }
-                    Utf8Rule::Lossy => {
-                        let mut vec_buf = vec![];
-                        cli_try!(file_ref.read_to_end(&mut vec_buf));
-                        buffer = String::from_utf8_lossy(&vec_buf).into_owned();
-                    }
-                    Utf8Rule::Strict => {
-                        cli_try!(file_ref.read_to_string(&mut buffer));
-                    }
+    // Counts `buffer`, the contents of `file`, adding any regions in other languages to the
+    // embedded counts of `count`, and the classification of each line to `lines` if given
+    fn count_buffer(cfg: &Config,
+                    count: &mut Count,
+                    res: &UnsafeRes,
+                    file: &PathBuf,
+                    buffer: &str,
+                    unjustified: &mut Vec<(PathBuf, u64)>,
+                    mut lines: Option<&mut Vec<(usize, Line)>>) {
+        if cfg.usafe && count.lang == Language::Rust && UnsafePolicy::is_crate_root(file) {
+            let policy = UnsafePolicy::from_source(buffer);
+            debugln!("found crate root; policy={:?}", policy);
+            count.crate_roots.push((file.clone(), policy));
+        }
+        if !embedded::is_host(&count.lang) {
+            Counts::count_lines(cfg,
+                                count,
+                                res,
+                                file,
+                                buffer.lines().enumerate(),
+                                unjustified,
+                                lines);
+            return;
+        }
+        for region in embedded::split(&count.lang, buffer, &cfg.langs) {
+            debugln!("iter; region.lang={:?}; lines={}", region.lang, region.lines.len());
+            let lines = lines.as_mut().map(|l| &mut **l);
+            if region.lang == count.lang {
+                Counts::count_lines(cfg,
+                                    count,
+                                    res,
+                                    file,
+                                    region.lines.into_iter(),
+                                    unjustified,
+                                    lines);
+                continue;
+            }
+            let i = match count.embedded.iter().position(|e| e.lang == region.lang) {
+                Some(i) => i,
+                None => {
+                    count.embedded.push(Count::new(region.lang, cfg.thousands));
+                    count.embedded.len() - 1
                 }
-                let mut is_in_comments = false;
-                let mut is_in_unsafe = false;
-                let mut bracket_count: i64 = 0;
+            };
+            let e = &mut count.embedded[i];
+            if e.files.last() != Some(file) {
+                e.add_file(file.clone());
+            }
+            let res = UnsafeRes::new(&e.lang);
+            Counts::count_lines(cfg,
+                                e,
+                                &res,
+                                file,
+                                region.lines.into_iter(),
+                                unjustified,
+                                lines);
+        }
+    }
 
-                'new_line: for line in buffer.lines() {
-                    let line = line.trim();
-                    debugln!("iter; line={:?};", line);
-                    count.lines += 1;
+    // Counts the `(line_no, line)` pairs of `lines`, all of which are in the language of `count`
+    fn count_lines<'a, I>(cfg: &Config,
+                          count: &mut Count,
+                          res: &UnsafeRes,
+                          file: &PathBuf,
+                          lines: I,
+                          unjustified: &mut Vec<(PathBuf, u64)>,
+                          mut classified: Option<&mut Vec<(usize, Line)>>)
+        where I: Iterator<Item = (usize, &'a str)>
+    {
+        let mut counter = LineCounter::new(cfg, count.lang.clone(), res);
+        let mut fc = FileCount::new(file.clone());
+        for (line_no, text) in lines {
+            let line = counter.classify(text);
+            let chars = text.chars().count() as u64;
+            fc.bytes += text.len() as u64;
+            fc.chars += chars;
+            fc.longest_line = cmp::max(fc.longest_line, chars);
+            fc.lines += 1;
+            match line.kind {
+                LineKind::Blank => fc.blanks += 1,
+                LineKind::Comment => fc.comments += 1,
+                LineKind::Code => fc.code += 1,
+            }
+            if line.usafe {
+                fc.usafe += 1;
+            }
+            if counter.is_unjustified() {
+                unjustified.push((file.clone(), line_no as u64 + 1));
+            }
+            if let Some(ref mut classified) = classified {
+                classified.push((line_no, line));
+            }
+        }
+        count.lines += fc.lines;
+        count.blanks += fc.blanks;
+        count.comments += fc.comments;
+        count.code += fc.code;
+        count.usafe += fc.usafe;
+        count.bytes += fc.bytes;
+        count.chars += fc.chars;
+        count.longest_line = cmp::max(count.longest_line, fc.longest_line);
+        // The regions of a file in the same language are counted one after another
+        if count.file_counts.last().map_or(false, |last| last.path == fc.path) {
+            count.file_counts.last_mut().unwrap().merge(&fc);
+        } else {
+            count.file_counts.push(fc);
+        }
+    }
 
-                    if is_in_comments {
-                        debugln!("still in comments");
-                        if line.contains(count.multi_end().unwrap()) {
-                            debugln!("line contained ending comment, stopping comments");
-                            is_in_comments = false;
-                        }
-                        count.comments += 1;
-                        continue;
-                    }
-                    debugln!("not in comments");
+    /// Counts `src`, the source of a file in `lang`, using the rules of `cfg`, and classifies
+    /// each of its lines
+    pub fn count_source(cfg: &Config, lang: Language, src: &str) -> SourceCount {
+        let res = UnsafeRes::new(&lang);
+        let mut count = Count::new(lang, cfg.thousands);
+        let mut lines = vec![];
+        Counts::count_buffer(cfg,
+                             &mut count,
+                             &res,
+                             &PathBuf::new(),
+                             src,
+                             &mut vec![],
+                             Some(&mut lines));
+        // Embedded regions were counted after the rest of the file
+        lines.sort_by_key(|&(line_no, _)| line_no);
+        SourceCount {
+            count: count,
+            classified: lines.into_iter().map(|(_, line)| line).collect(),
+        }
+    }
 
-                    if line.trim().is_empty() {
-                        debugln!("line was empty");
-                        count.blanks += 1;
-                        continue;
-                    }
-                    debugln!("Line isn't empty");
+    // Attributes the regions embedded in other files to the rows of their own languages
+    fn merge_embedded(&mut self) {
+        let mut embedded = vec![];
+        for count in self.counts.iter_mut() {
+            embedded.extend(mem::replace(&mut count.embedded, vec![]));
+        }
+        for e in embedded {
+            if let Some(count) = self.counts.iter_mut().find(|c| c.lang == e.lang) {
+                count.merge(&e);
+                continue;
+            }
+            let mut count = Count::new(e.lang.clone(), self.cfg.thousands);
+            count.merge(&e);
+            self.counts.push(count);
+        }
+    }
 
-                    if let Some(ms) = count.multi_start() {
-                        debugln!("This file type has a multi start of: {:?}", ms);
-                        if line.starts_with(ms) {
-                            debugln!("line starts with multi comment");
-                            count.comments += 1;
-                            is_in_comments = !line.contains(count.multi_end().unwrap());
-                            debugln!("line also contained a multi end: {:?}", is_in_comments);
-                            continue;
-                        } else if line.contains(ms) {
-                            debugln!("line contains a multi start");
-                            is_in_comments = !line.contains(count.multi_end().unwrap());
-                            debugln!("line also contained a multi end: {:?}", is_in_comments);
-                            if is_in_comments {
-                                continue;
-                            }
-                        }
-                    } else {
-                        debugln!("No multi line comments for this type");
-                    }
-                    debugln!("No multi line comments for this line");
+    /// The count of each language, in the order they were first found
+    pub fn counts(&self) -> &[Count] {
+        &self.counts
+    }
 
-                    if let Some(single_comments) = count.single() {
-                        debugln!("This type has single line comments: {:?}", single_comments);
-                        for single in single_comments {
-                            if line.starts_with(single) {
-                                debugln!("Line started with a comment");
-                                count.comments += 1;
-                                continue 'new_line;
-                            } else {
-                                debugln!("Line dind't start with a comment");
-                            }
-                        }
-                    } else {
-                        debugln!("No single line comments for this type");
-                    }
+    /// The number of files counted
+    pub fn total_files(&self) -> u64 {
+        self.tot as u64
+    }
 
-                    if self.cfg.usafe && count.lang.is_unsafe() {
-                        debugln!("Calculating --unsafe-statistics");
-                        debugln!("The language is not safe");
-                        if let Some(..) = count.lang.unsafe_keyword() {
-                            debugln!("There is a keyword");
-                            debugln!("line={:?}", line);
-                            if is_in_unsafe {
-                                debugln!("It didn't contain the keyword, but we are still in \
-                                          unsafe");
-                                count.usafe += 1;
-                                bracket_count = Counts::count_brackets(line, Some(bracket_count));
-                                is_in_unsafe = bracket_count > 0;
-                                debugln!("after counting brackets; is_in_unsafe={:?}; \
-                                          bracket_count={:?}",
-                                         is_in_unsafe,
-                                         bracket_count);
-                            } else if let Some(caps) = re.captures(line) {
-                                let mut should_count = true;
-                                if let Some(before) = caps.at(1) {
-                                    if let Some(single_v) = count.lang.single() {
-                                        for s in single_v {
-                                            if before.contains(s) {
-                                                should_count = false;
-                                                break;
-                                            }
-                                        }
-                                    }
-                                    if let Some(multi) = count.lang.multi_start() {
-                                        if before.contains(multi) &&
-                                           !before.contains(count.lang.multi_end().unwrap()) {
-                                            should_count = false;
-                                        }
-                                    }
-                                }
-                                if should_count {
-                                    debugln!("It contained the keyword; usafe_line={:?}", line);
-                                    count.usafe += 1;
-                                    if let Some(after) = caps.at(3) {
-                                        debugln!("after_usafe={:?}", after);
-                                        bracket_count = Counts::count_brackets(after, None);
-                                        is_in_unsafe = bracket_count > 0;
-                                        debugln!("after counting brackets; is_in_unsafe={:?}; \
-                                                  bracket_count={:?}",
-                                                 is_in_unsafe,
-                                                 bracket_count);
-                                    }
-                                }
-                            } else {
-                                debugln!("It didn't contain the keyword, and we are not in unsafe");
-                            }
+    /// The number of lines of code in all languages
+    pub fn total_code(&self) -> u64 {
+        self.tot_code
+    }
 
-                            if bracket_count < 0 {
-                                debugln!("bracket_count < 0; resetting");
-                                bracket_count = 0
-                            }
-                        } else {
-                            debugln!("Language is unsafe, incing the count");
-                            count.usafe += 1;
-                        }
-                    }
-                    count.code += 1;
-                }
-            }
-            self.tot += count.files.len();
-            self.tot_lines += count.lines;
-            self.tot_comments += count.comments;
-            self.tot_blanks += count.blanks;
-            self.tot_code += count.code;
-            self.tot_usafe += count.usafe;
+    /// The number of unsafe lines in all languages
+    pub fn total_unsafe(&self) -> u64 {
+        self.tot_usafe
+    }
+
+    /// The totals of each group of files given by `group_by`, sorted as configured
+    pub fn groups(&self) -> Vec<Group> {
+        let mut groups = Group::group(&self.counts, self.cfg.group_by);
+        if let Some(sort) = self.cfg.sort {
+            group::sort(&mut groups, sort, self.cfg.reverse, group::group_key);
         }
+        groups
+    }
 
-        Ok(())
+    /// The totals of all languages
+    pub fn totals(&self) -> Group {
+        Group {
+            name: "Totals".to_owned(),
+            files: self.tot as u64,
+            code: self.tot_code,
+            comments: self.tot_comments,
+            blanks: self.tot_blanks,
+            lines: self.tot_lines,
+            usafe: self.tot_usafe,
+            bytes: self.tot_bytes,
+            chars: self.tot_chars,
+            longest_line: self.tot_longest,
+        }
     }
 
+    /// The totals of each file in each language found in it, in order of their paths unless
+    /// sorted otherwise
+    pub fn files(&self) -> Vec<(Language, &FileCount)> {
+        let mut files: Vec<_> = self.counts
+            .iter()
+            .chain(self.counts.iter().flat_map(|c| c.embedded.iter()))
+            .flat_map(|c| c.file_counts.iter().map(move |fc| (c.lang.clone(), fc)))
+            .collect();
+        files.sort_by(|a, b| a.1.path.cmp(&b.1.path).then(a.0.name().cmp(b.0.name())));
+        if let Some(sort) = self.cfg.sort {
+            group::sort(&mut files, sort, self.cfg.reverse, group::file_key);
+        }
+        files
+    }
+
+    /// The totals of each directory holding the files counted, down to `depth` levels below the
+    /// deepest one holding all of them
+    pub fn dirs(&self, depth: usize) -> Dir {
+        Dir::tree(&self.counts, depth)
+    }
+
+    /// Writes the results to stdout in the configured format
     pub fn write_results(&mut self) -> CliResult<()> {
-        let mut w = TabWriter::new(vec![]);
-        cli_try!(write!(w,
-                        "\tLanguage\tFiles\tLines\tBlanks\tComments\tCode{}\n",
-                        if self.cfg.usafe { "\tUnsafe (%)" } else { "" }));
-        cli_try!(write!(w,
-                        "\t--------\t-----\t-----\t------\t--------\t----{}\n",
-                        if self.cfg.usafe { "\t----------" } else { "" }));
-        for count in &self.counts {
-            if self.cfg.usafe {
-                let usafe_per = if count.code != 0 {
-                    (count.usafe as f64 / count.code as f64) * 100.00f64
-                } else {
-                    0f64
-                };
-                cli_try!(write!(w,
-                                "\t{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
-                                count.lang.name(),
-                                count.total_files(),
-                                count.lines(),
-                                count.blanks(),
-                                count.comments(),
-                                count.code(),
-                                if (usafe_per - 00f64).abs() < f64::EPSILON {
-                                    "".to_owned()
-                                } else {
-                                    format!("{} ({:.2}%)", count.usafe(), usafe_per)
-                                }));
-            } else {
-                cli_try!(write!(w, "\t{}\n", count));
-            }
+        report::write(self.cfg, self)
+    }
+
+    /// The files skipped because their language wasn't recognized, with their sizes in bytes
+    pub fn skipped(&self) -> &[(PathBuf, u64)] {
+        &self.skipped
+    }
+
+    /// The files whose language wasn't recognized, as `(extension, files, bytes)` grouped by
+    /// extension and sorted by the number of files
+    pub fn skipped_by_ext(&self) -> Vec<(String, u64, u64)> {
+        let mut by_ext = BTreeMap::new();
+        for &(ref file, size) in &self.skipped {
+            let ext = file.extension()
+                .map_or_else(|| "(none)".to_owned(), |e| e.to_string_lossy().into_owned());
+            let e = by_ext.entry(ext).or_insert((0, 0));
+            e.0 += 1;
+            e.1 += size;
         }
-        cli_try!(write!(w,
-                        "\t--------\t-----\t-----\t------\t--------\t----{}\n",
-                        if self.cfg.usafe { "\t----------" } else { "" }));
-        cli_try!(write!(w,
-                        "{}\t\t{}\t{}\t{}\t{}\t{}{}\n",
-                        "Totals:",
-                        fmt::format_number(self.tot as u64, self.cfg.thousands),
-                        fmt::format_number(self.tot_lines, self.cfg.thousands),
-                        fmt::format_number(self.tot_blanks, self.cfg.thousands),
-                        fmt::format_number(self.tot_comments, self.cfg.thousands),
-                        fmt::format_number(self.tot_code, self.cfg.thousands),
-                        if self.cfg.usafe {
-                            format!("\t{} ({:.2}%)",
-                                    fmt::format_number(self.tot_usafe, self.cfg.thousands),
-                                    (self.tot_usafe as f64 / self.tot_code as f64) * 100.00f64)
-                        } else {
-                            "".to_owned()
-                        }));
+        let mut ret: Vec<_> = by_ext.into_iter().map(|(ext, (n, size))| (ext, n, size)).collect();
+        ret.sort_by(|a, b| b.1.cmp(&a.1));
+        ret
+    }
 
-        cli_try!(w.flush());
+    /// The `unsafe` blocks which weren't preceded by a comment containing the configured safety
+    /// marker, as the path and line of each, if they were checked
+    pub fn unjustified(&self) -> &[(PathBuf, u64)] {
+        &self.unjustified
+    }
 
-        verboseln!(self.cfg,
-                   "{} {}",
-                   Format::Good("Displaying"),
-                   "the results:");
-        if self.tot > 0 {
-            write!(io::stdout(),
-                   "{}",
-                   String::from_utf8(w.unwrap()).ok().expect("failed to get valid UTF-8 String"))
-                .expect("failed to write output");
-        } else {
-            println!("\n\tNo source files were found matching the specified criteria");
+    /// Prints every `unsafe` block which wasn't preceded by a comment containing the configured
+    /// safety marker to stderr, so that it doesn't mix with the results, and returns an error if
+    /// there were any
+    pub fn check_safety(&self) -> CliResult<()> {
+        if self.unjustified.is_empty() {
+            verboseln!(self.cfg,
+                       "{} unsafe blocks have a '{}' comment",
+                       Format::Good("All"),
+                       self.cfg.safety_marker);
+            return Ok(());
         }
-        Ok(())
+        wlnerr!("");
+        for &(ref file, line) in &self.unjustified {
+            wlnerr!("{}:{}: {}",
+                    file.display(),
+                    line,
+                    Format::Warning("unsafe block without a safety comment"));
+        }
+        let n = self.unjustified.len();
+        Err(CliError::MissingSafety(format!("{} unsafe block{} missing a '{}' comment",
+                                            n,
+                                            if n == 1 { " is" } else { "s are" },
+                                            self.cfg.safety_marker)))
     }
+}
 
-    fn count_brackets(line: &str, count: Option<i64>) -> i64 {
-        let mut b: i64 = count.unwrap_or(0);
-        for c in line.chars() {
-            match c {
-                '{' => b += 1,
-                '}' => b -= 1,
-                _ => (),
+// Reads all of `r`, handling invalid UTF-8 as `rule` says, or None if it's to be ignored
+fn read_source<R: Read>(rule: Utf8Rule, mut r: R) -> CliResult<Option<String>> {
+    let mut buffer = String::new();
+    match rule {
+        Utf8Rule::Ignore => {
+            if let Err(..) = r.read_to_string(&mut buffer) {
+                return Ok(None);
             }
         }
-        b
+        Utf8Rule::Lossy => {
+            let mut vec_buf = vec![];
+            cli_try!(r.read_to_end(&mut vec_buf));
+            buffer = String::from_utf8_lossy(&vec_buf).into_owned();
+        }
+        Utf8Rule::Strict => {
+            cli_try!(r.read_to_string(&mut buffer));
+        }
     }
+    Ok(Some(buffer))
 }
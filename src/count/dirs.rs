@@ -0,0 +1,85 @@
+use count::{self, Count, FileCount};
+
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+/// The totals of a directory and everything below it, with those of each of its subdirectories
+#[derive(Debug)]
+pub struct Dir {
+    /// The totals of the files in the directory and its subdirectories, with the path of the
+    /// directory, relative to the current directory if it's inside it
+    pub totals: FileCount,
+    /// The number of files in the directory and its subdirectories
+    pub files: u64,
+    /// The subdirectories, sorted by name
+    pub dirs: Vec<Dir>,
+}
+
+impl Dir {
+    fn new(path: PathBuf) -> Self {
+        Dir {
+            totals: FileCount::new(path),
+            files: 0,
+            dirs: vec![],
+        }
+    }
+
+    /// The tree of the directories holding the files of `counts`, and any code embedded in them,
+    /// rooted at the deepest directory holding all of them and going at most `depth` levels
+    /// below it. Files in deeper directories are rolled up into their ancestor at `depth`.
+    pub fn tree(counts: &[Count], depth: usize) -> Self {
+        let files = count::by_file(counts);
+        let root = common_dir(files.keys().map(|p| p.parent().unwrap_or(Path::new(""))));
+        let mut tree = Dir::new(count::relative_dir(&root));
+        // The files are in order, so the subdirectories are added in order as well
+        for (path, fc) in files {
+            let parent = path.parent().unwrap_or(Path::new(""));
+            let rel = parent.strip_prefix(&root).unwrap_or(parent);
+            tree.add(rel.components().map(|c| c.as_os_str()).take(depth), &fc);
+        }
+        tree
+    }
+
+    // Adds a file in the subdirectory `dirs` of this one
+    fn add<'a, I>(&mut self, mut dirs: I, fc: &FileCount)
+        where I: Iterator<Item = &'a OsStr>
+    {
+        self.files += 1;
+        self.totals.merge(fc);
+        if let Some(name) = dirs.next() {
+            let path = if self.totals.path == Path::new(".") {
+                PathBuf::from(name)
+            } else {
+                self.totals.path.join(name)
+            };
+            let i = match self.dirs.iter().position(|d| d.totals.path == path) {
+                Some(i) => i,
+                None => {
+                    self.dirs.push(Dir::new(path));
+                    self.dirs.len() - 1
+                }
+            };
+            self.dirs[i].add(dirs, fc);
+        }
+    }
+}
+
+// The deepest directory which holds all of `dirs`
+fn common_dir<'a, I>(dirs: I) -> PathBuf
+    where I: Iterator<Item = &'a Path>
+{
+    let mut common: Option<PathBuf> = None;
+    for dir in dirs {
+        common = Some(match common {
+            None => dir.to_path_buf(),
+            Some(c) => {
+                c.components()
+                    .zip(dir.components())
+                    .take_while(|&(a, b)| a == b)
+                    .map(|(a, _)| a.as_os_str())
+                    .collect()
+            }
+        });
+    }
+    common.unwrap_or_default()
+}
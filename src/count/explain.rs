@@ -0,0 +1,125 @@
+use config::{Config, OutputFormat};
+use count::line::{LineCounter, LineKind, UnsafeRes};
+use error::{CliError, CliResult};
+use fmt;
+use language::Language;
+use language::embedded::{self, Region};
+
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+// A line of the file, how it was classified, and what was left open after it
+struct Explained<'a> {
+    line_no: usize,
+    text: &'a str,
+    lang: Language,
+    kind: &'static str,
+    state: Vec<String>,
+}
+
+static KINDS: [&'static str; 6] = ["blank", "comment", "doc", "code", "mixed", "unsafe"];
+
+/// Writes each line of `file` along with how it's classified when counting, and the comments,
+/// strings and unsafe blocks left open after it, so that unexpected counts can be diagnosed
+pub fn explain(cfg: &Config, file: &Path) -> CliResult<()> {
+    debugln!("executing; explain; file={:?};", file);
+    let lang = match cfg.langs.detect(file) {
+        Some(lang) => lang,
+        None => {
+            return Err(CliError::Generic(format!("the language of '{}' isn't known",
+                                                 file.display())))
+        }
+    };
+    let mut buf = vec![];
+    cli_try!(cli_try!(File::open(file)).read_to_end(&mut buf));
+    let src = String::from_utf8_lossy(&buf);
+
+    let regions = if embedded::is_host(&lang) {
+        embedded::split(&lang, &src, &cfg.langs)
+    } else {
+        vec![Region {
+                 lang: lang.clone(),
+                 lines: src.lines().enumerate().collect(),
+             }]
+    };
+    // The regions are in the order they appear, and each is counted with a new counter just as
+    // `Counts::count` does
+    let mut lines = vec![];
+    for region in regions {
+        let res = UnsafeRes::new(&region.lang);
+        let mut counter = LineCounter::detailed(cfg, region.lang.clone(), &res);
+        for (line_no, text) in region.lines {
+            let line = counter.classify(text);
+            let kind = match line.kind {
+                LineKind::Blank => "blank",
+                LineKind::Comment if counter.is_doc() => "doc",
+                LineKind::Comment => "comment",
+                LineKind::Code if line.usafe => "unsafe",
+                LineKind::Code if counter.is_mixed() => "mixed",
+                LineKind::Code => "code",
+            };
+            lines.push(Explained {
+                line_no: line_no + 1,
+                text: text,
+                lang: region.lang.clone(),
+                kind: kind,
+                state: counter.state(),
+            });
+        }
+    }
+
+    match cfg.output {
+        OutputFormat::Json => write_json(file, &lang, &lines),
+        _ => write_text(file, &lang, &lines),
+    }
+}
+
+fn write_text(file: &Path, lang: &Language, lines: &[Explained]) -> CliResult<()> {
+    let states: Vec<String> = lines.iter()
+        .map(|l| {
+            let mut state = l.state.join(", ");
+            if l.lang != *lang {
+                state = format!("[{}] {}", l.lang.name(), state);
+            }
+            state.trim_right().to_owned()
+        })
+        .collect();
+    let width = states.iter().map(|s| s.chars().count()).max().unwrap_or(0);
+    let stdout = io::stdout();
+    let mut w = stdout.lock();
+    cli_try!(writeln!(w, "{} ({})", file.display(), lang.name()));
+    for (l, state) in lines.iter().zip(states.iter()) {
+        cli_try!(writeln!(w,
+                          "{:>5} {:<7} {:<width$} | {}",
+                          l.line_no,
+                          l.kind,
+                          state,
+                          l.text,
+                          width = width));
+    }
+    let totals: Vec<String> = KINDS.iter()
+        .map(|kind| format!("{} {}", lines.iter().filter(|l| l.kind == *kind).count(), kind))
+        .collect();
+    cli_try!(writeln!(w, "\n{} lines: {}", lines.len(), totals.join(", ")));
+    Ok(())
+}
+
+fn write_json(file: &Path, lang: &Language, lines: &[Explained]) -> CliResult<()> {
+    let lines: Vec<String> = lines.iter()
+        .map(|l| {
+            let state: Vec<String> = l.state.iter().map(|s| fmt::json_str(s)).collect();
+            format!("{{\"line\":{},\"language\":{},\"kind\":{},\"state\":[{}],\"text\":{}}}",
+                    l.line_no,
+                    fmt::json_str(l.lang.name()),
+                    fmt::json_str(l.kind),
+                    state.join(","),
+                    fmt::json_str(l.text))
+        })
+        .collect();
+    println!("{{\"file\":{},\"language\":{},\"lines\":[{}]}}",
+             fmt::json_str(&file.to_string_lossy()),
+             fmt::json_str(lang.name()),
+             lines.join(","));
+    Ok(())
+}
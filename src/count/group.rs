@@ -0,0 +1,218 @@
+use config::{GroupBy, SortBy};
+use count::{self, Count, FileCount, Metrics};
+use language::Language;
+use toml::{Parser, Value};
+
+use std::cmp;
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// The totals of the files in a group, such as a directory or crate
+#[derive(Debug)]
+pub struct Group {
+    /// The name of the group, i.e. a language, the path of a directory or the name of a crate
+    pub name: String,
+    /// The number of files in the group
+    pub files: u64,
+    /// The lines of code, which includes lines with both code and a comment
+    pub code: u64,
+    /// The lines holding only comments
+    pub comments: u64,
+    /// The empty lines, or those holding only whitespace
+    pub blanks: u64,
+    /// All lines
+    pub lines: u64,
+    /// The lines of code which are unsafe, if unsafe statistics were requested
+    pub usafe: u64,
+    /// The bytes of the lines, not including line endings
+    pub bytes: u64,
+    /// The characters of the lines, not including line endings
+    pub chars: u64,
+    /// The characters in the longest line
+    pub longest_line: u64,
+}
+
+impl Group {
+    fn new(name: String) -> Self {
+        Group {
+            name: name,
+            files: 0,
+            code: 0,
+            comments: 0,
+            blanks: 0,
+            lines: 0,
+            usafe: 0,
+            bytes: 0,
+            chars: 0,
+            longest_line: 0,
+        }
+    }
+
+    fn add(&mut self, fc: &FileCount) {
+        self.files += 1;
+        self.code += fc.code;
+        self.comments += fc.comments;
+        self.blanks += fc.blanks;
+        self.lines += fc.lines;
+        self.usafe += fc.usafe;
+        self.bytes += fc.bytes;
+        self.chars += fc.chars;
+        self.longest_line = cmp::max(self.longest_line, fc.longest_line);
+    }
+
+    /// The values derived from the totals
+    pub fn metrics(&self) -> Metrics {
+        Metrics::new(self.files, self.lines, self.blanks, self.comments, self.code, self.usafe)
+    }
+
+    /// The files of `counts`, including any code embedded in them, totaled by `by` and in order
+    /// of their names. Grouping by language gives a group for each of `counts`, in the same order.
+    pub fn group(counts: &[Count], by: GroupBy) -> Vec<Group> {
+        if by == GroupBy::Language {
+            return counts.iter().map(Group::from).collect();
+        }
+        let cwd = env::current_dir().unwrap_or_default();
+        let mut crates = Crates { dirs: HashMap::new() };
+        let mut groups: BTreeMap<String, Group> = BTreeMap::new();
+        for (path, fc) in count::by_file(counts) {
+            let dir = path.parent().unwrap_or(Path::new(""));
+            let name = match by {
+                GroupBy::Dir => count::relative_dir(dir).display().to_string(),
+                GroupBy::Extension => {
+                    path.extension()
+                        .map_or_else(|| "(none)".to_owned(), |e| e.to_string_lossy().into_owned())
+                }
+                GroupBy::Crate | GroupBy::Target => {
+                    match crates.find(&cwd.join(dir)) {
+                        Some((root, name)) if by == GroupBy::Target => {
+                            format!("{} ({})", name, target(&root, &cwd.join(path)))
+                        }
+                        Some((_, name)) => name,
+                        None => "(none)".to_owned(),
+                    }
+                }
+                GroupBy::Language => unreachable!(),
+            };
+            groups.entry(name.clone()).or_insert_with(|| Group::new(name)).add(&fc);
+        }
+        groups.into_iter().map(|(_, g)| g).collect()
+    }
+}
+
+/// Sorts `rows` by `sort`, the largest first or by name alphabetically, using the values given by
+/// `key`. Rows which are equal are kept in order of their names.
+pub fn sort<T, F>(rows: &mut [T], sort: SortBy, reverse: bool, key: F)
+    where F: Fn(&T) -> (&str, [u64; 6])
+{
+    rows.sort_by(|a, b| {
+        let ((a_name, a), (b_name, b)) = (key(a), key(b));
+        let i = match sort {
+            SortBy::Lines => Some(0),
+            SortBy::Code => Some(1),
+            SortBy::Comments => Some(2),
+            SortBy::Blanks => Some(3),
+            SortBy::Files => Some(4),
+            SortBy::Unsafe => Some(5),
+            SortBy::Name => None,
+        };
+        let ord = match i {
+            Some(i) => b[i].cmp(&a[i]).then(a_name.cmp(b_name)),
+            None => a_name.cmp(b_name),
+        };
+        if reverse { ord.reverse() } else { ord }
+    });
+}
+
+/// The values a `Group` is sorted by, as `sort` takes them
+pub fn group_key(g: &Group) -> (&str, [u64; 6]) {
+    (&g.name, [g.lines, g.code, g.comments, g.blanks, g.files, g.usafe])
+}
+
+/// The values a `Count` is sorted by, as `sort` takes them
+pub fn count_key(c: &Count) -> (&str, [u64; 6]) {
+    (c.lang.name(), [c.lines, c.code, c.comments, c.blanks, c.files.len() as u64, c.usafe])
+}
+
+/// The values the totals of a file in a language are sorted by, as `sort` takes them
+pub fn file_key<'a>(f: &'a (Language, &FileCount)) -> (&'a str, [u64; 6]) {
+    let fc = f.1;
+    (fc.path.to_str().unwrap_or(""), [fc.lines, fc.code, fc.comments, fc.blanks, 1, fc.usafe])
+}
+
+impl<'a> From<&'a Count> for Group {
+    /// The totals of a language
+    fn from(c: &Count) -> Self {
+        Group {
+            name: c.lang.name().to_owned(),
+            files: c.files.len() as u64,
+            code: c.code,
+            comments: c.comments,
+            blanks: c.blanks,
+            lines: c.lines,
+            usafe: c.usafe,
+            bytes: c.bytes,
+            chars: c.chars,
+            longest_line: c.longest_line,
+        }
+    }
+}
+
+// The crates found holding each directory searched, as `(root, name)`
+struct Crates {
+    dirs: HashMap<PathBuf, Option<(PathBuf, String)>>,
+}
+
+impl Crates {
+    // The crate holding the absolute path `dir`, which is the nearest directory above it with a
+    // `Cargo.toml` for a package rather than just a workspace
+    fn find(&mut self, dir: &Path) -> Option<(PathBuf, String)> {
+        if let Some(found) = self.dirs.get(dir) {
+            return found.clone();
+        }
+        let found = match package_name(&dir.join("Cargo.toml")) {
+            Some(name) => Some((dir.to_path_buf(), name)),
+            None => dir.parent().and_then(|parent| self.find(parent)),
+        };
+        self.dirs.insert(dir.to_path_buf(), found.clone());
+        found
+    }
+}
+
+// The name of the package of the manifest at `path`, if it exists and has one
+fn package_name(path: &Path) -> Option<String> {
+    let mut s = String::new();
+    if File::open(path).and_then(|mut f| f.read_to_string(&mut s)).is_err() {
+        return None;
+    }
+    Parser::new(&s)
+        .parse()
+        .and_then(|table| {
+            table.get("package")
+                .and_then(|p| p.lookup("name"))
+                .and_then(Value::as_str)
+                .map(|name| name.to_owned())
+        })
+}
+
+// The kind of target of the crate at `root` which `file` belongs to, following Cargo's layout
+fn target(root: &Path, file: &Path) -> String {
+    let rel = file.strip_prefix(root).unwrap_or(file);
+    let parts: Vec<String> = rel.iter().map(|p| p.to_string_lossy().into_owned()).collect();
+    let part = |i: usize| parts.get(i).map_or("", |p| &**p);
+    match part(0) {
+        "build.rs" if parts.len() == 1 => "build script".to_owned(),
+        "src" if parts.len() == 2 && part(1) == "main.rs" => "bin".to_owned(),
+        // `src/bin/name.rs` or `src/bin/name/main.rs`
+        "src" if parts.len() > 2 && part(1) == "bin" => {
+            format!("bin {}",
+                    Path::new(part(2)).file_stem().map_or("".into(), |s| s.to_string_lossy()))
+        }
+        "src" if root.join("src").join("lib.rs").is_file() => "lib".to_owned(),
+        "src" => "bin".to_owned(),
+        "tests" | "benches" | "examples" => part(0).to_owned(),
+        _ => "other".to_owned(),
+    }
+}
@@ -0,0 +1,648 @@
+use comment::{Block, Comment};
+use config::Config;
+use language::{self, heredoc, python, Language};
+use regex::Regex;
+
+/// How a line of source was counted
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum LineKind {
+    /// Empty, or holding only whitespace
+    Blank,
+    /// Holding only a comment, or part of a docstring
+    Comment,
+    /// Holding code, possibly along with a comment
+    Code,
+}
+
+/// How a single line of source was counted
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Line {
+    /// The language of the line, which differs from that of its file for embedded code such as
+    /// the `<script>` blocks of HTML
+    pub lang: Language,
+    /// Whether the line is blank, a comment or code
+    pub kind: LineKind,
+    /// Whether the line is unsafe code, if unsafe statistics were requested
+    pub usafe: bool,
+}
+
+/// The regexes matching the unsafe keyword and risky patterns of a language, and the start of
+/// its heredocs, compiled once for all of its files
+#[derive(Debug)]
+pub struct UnsafeRes {
+    keyword: Regex,
+    block: Option<Regex>,
+    patterns: Option<Regex>,
+    heredoc: Option<Regex>,
+}
+
+impl UnsafeRes {
+    pub fn new(lang: &Language) -> Self {
+        let keyword = if let Some(kw) = lang.unsafe_keyword() {
+            Regex::new(&language::unsafe_keyword_regex(kw)).unwrap()
+        } else {
+            Regex::new("").unwrap()
+        };
+        UnsafeRes {
+            keyword: keyword,
+            block: lang.unsafe_keyword()
+                .map(|kw| Regex::new(&language::unsafe_block_regex(kw)).unwrap()),
+            patterns: lang.unsafe_patterns()
+                .map(|pats| Regex::new(&*format!("(.*?)(?:{})", pats.join("|"))).unwrap()),
+            heredoc: heredoc::regex(lang),
+        }
+    }
+}
+
+/// Classifies the lines of a file in a single language one at a time, tracking the comments,
+/// strings and unsafe blocks which span several lines
+pub struct LineCounter<'a> {
+    lang: Language,
+    res: &'a UnsafeRes,
+    usafe: bool,
+    check_safety: bool,
+    marker: &'a str,
+    blocks: Vec<Block<String>>,
+    open_block: Option<Block<String>>,
+    single: Option<Vec<String>>,
+    strings: Vec<String>,
+    // The delimiter of a string literal left open by the last line, i.e. a multi-line `"`
+    open_string: Option<String>,
+    // Whether the open block comment is a doc comment
+    open_doc: bool,
+    is_in_unsafe: bool,
+    bracket_count: i64,
+    has_safety_comment: bool,
+    unjustified: bool,
+    // Whether the last line was part of a doc comment, and if it was code with a comment
+    doc: bool,
+    mixed: bool,
+    // Whether to work out if lines of code are mixed, which only `--explain` needs
+    details: bool,
+    py_strings: Option<python::Strings>,
+    heredocs: Option<heredoc::Heredocs<'a>>,
+}
+
+impl<'a> LineCounter<'a> {
+    pub fn new(cfg: &'a Config, lang: Language, res: &'a UnsafeRes) -> Self {
+        LineCounter {
+            res: res,
+            usafe: (cfg.usafe || cfg.check_safety) && lang.is_unsafe(),
+            check_safety: cfg.check_safety,
+            marker: cfg.safety_marker,
+            // Empty delimiters would match every line
+            blocks: lang.multi()
+                .into_iter()
+                .filter(|b| !b.start.is_empty() && !b.end.is_empty())
+                .collect(),
+            open_block: None,
+            single: lang.single().map(|v| v.into_iter().filter(|s| !s.is_empty()).collect()),
+            strings: lang.strings(),
+            open_string: None,
+            open_doc: false,
+            is_in_unsafe: false,
+            bracket_count: 0,
+            has_safety_comment: false,
+            unjustified: false,
+            doc: false,
+            mixed: false,
+            details: false,
+            py_strings: if lang == Language::Python {
+                Some(python::Strings::new())
+            } else {
+                None
+            },
+            heredocs: res.heredoc.as_ref().map(|re| heredoc::Heredocs::new(&lang, re)),
+            lang: lang,
+        }
+    }
+
+    /// A counter which also tracks which lines of code have comments, and always finds the
+    /// unsafe lines of languages which have them
+    pub fn detailed(cfg: &'a Config, lang: Language, res: &'a UnsafeRes) -> Self {
+        let usafe = lang.is_unsafe();
+        let mut counter = LineCounter::new(cfg, lang, res);
+        counter.usafe = usafe;
+        counter.details = true;
+        counter
+    }
+
+    /// Whether the last line classified was part of a doc comment or docstring
+    pub fn is_doc(&self) -> bool {
+        self.doc
+    }
+
+    /// Whether the last line classified was code with a comment, which is only known for a
+    /// `detailed` counter
+    pub fn is_mixed(&self) -> bool {
+        self.mixed
+    }
+
+    /// What was left open by the last line classified, i.e. a block comment or unsafe block
+    pub fn state(&self) -> Vec<String> {
+        let mut state = vec![];
+        if let Some(ref block) = self.open_block {
+            state.push(format!("in {} {}", block.start, block.end));
+        }
+        if let Some(ref delim) = self.open_string {
+            state.push(format!("in {} string", delim));
+        }
+        if let Some((quotes, is_doc)) = self.py_strings.as_ref().and_then(|s| s.open()) {
+            state.push(format!("in {} {}", quotes, if is_doc { "docstring" } else { "string" }));
+        }
+        if let Some(term) = self.heredocs.as_ref().and_then(|h| h.pending()) {
+            state.push(format!("heredoc until {}", term));
+        }
+        if self.is_in_unsafe {
+            state.push(format!("unsafe depth {}", self.bracket_count));
+        }
+        state
+    }
+
+    /// Whether the last line classified opened an unsafe block without a safety comment
+    pub fn is_unjustified(&self) -> bool {
+        self.unjustified
+    }
+
+    fn line(&self, kind: LineKind, usafe: bool) -> Line {
+        Line {
+            lang: self.lang.clone(),
+            kind: kind,
+            usafe: usafe,
+        }
+    }
+
+    /// Classifies the next line of the file
+    pub fn classify(&mut self, line: &str) -> Line {
+        let line = line.trim();
+        debugln!("iter; line={:?};", line);
+        self.unjustified = false;
+        self.doc = false;
+        self.mixed = false;
+
+        match self.py_strings.as_mut().and_then(|s| s.classify(line)) {
+            Some(python::Line::Docstring) => {
+                debugln!("line is part of a docstring");
+                self.doc = true;
+                return self.line(LineKind::Comment, false);
+            }
+            Some(python::Line::String) => {
+                debugln!("line is part of a multi-line string");
+                return self.line(LineKind::Code, false);
+            }
+            None => (),
+        }
+        if self.heredocs.as_mut().map_or(false, |h| h.in_body(line)) {
+            debugln!("line is part of a heredoc");
+            return self.line(LineKind::Code, false);
+        }
+
+        // The rest of a line which closes a string is scanned with the string blanked out
+        let rest;
+        let line = match self.open_string.take() {
+            Some(delim) => {
+                match close_string(line, 0, &delim) {
+                    Some(end) => {
+                        debugln!("line closes a multi-line string");
+                        rest = format!("{}{}", " ".repeat(end), &line[end..]);
+                        &*rest
+                    }
+                    None => {
+                        debugln!("line is part of a multi-line string");
+                        self.open_string = Some(delim);
+                        return self.line(LineKind::Code, false);
+                    }
+                }
+            }
+            None => line,
+        };
+
+        if let Some(ref block) = self.open_block {
+            debugln!("still in comments");
+            if line.contains(self.marker) {
+                self.has_safety_comment = true;
+            }
+            if closes(block, line) {
+                debugln!("line contained ending comment, stopping comments");
+                self.open_block = None;
+            }
+            self.doc = self.open_doc;
+            return self.line(LineKind::Comment, false);
+        }
+        debugln!("not in comments");
+
+        if line.is_empty() {
+            debugln!("line was empty");
+            return self.line(LineKind::Blank, false);
+        }
+        debugln!("Line isn't empty");
+
+        if let Some(block) = self.blocks.iter().find(|b| line.starts_with(&*b.start)).cloned() {
+            debugln!("line starts with multi comment; block={:?}", block);
+            if line.contains(self.marker) {
+                self.has_safety_comment = true;
+            }
+            self.doc = is_doc(line, &block.start, Some(&block.end));
+            if block.line_start || !line[block.start.len()..].contains(&*block.end) {
+                self.open_block = Some(block);
+                self.open_doc = self.doc;
+            }
+            debugln!("line also contained a multi end: {:?}", self.open_block.is_none());
+            return self.line(LineKind::Comment, false);
+        } else if let Some((pos, block)) = find_block(line, &self.blocks, &self.strings) {
+            // The code before the comment makes this a line of code
+            debugln!("line contains a multi start; block={:?}", block);
+            self.mixed = true;
+            if !line[pos + block.start.len()..].contains(&*block.end) {
+                self.open_doc = is_doc(&line[pos..], &block.start, Some(&block.end));
+                self.open_block = Some(block);
+            }
+            debugln!("line also contained a multi end: {:?}", self.open_block.is_none());
+        }
+        debugln!("No multi line comments for this line");
+
+        if let Some(ref single_comments) = self.single {
+            debugln!("This type has single line comments: {:?}", single_comments);
+            if let Some(single) = single_comments.iter().find(|s| line.starts_with(&**s)) {
+                debugln!("Line started with a comment");
+                self.doc = is_doc(line, single, None);
+                if line.contains(self.marker) {
+                    self.has_safety_comment = true;
+                }
+                return self.line(LineKind::Comment, false);
+            }
+        } else {
+            debugln!("No single line comments for this type");
+        }
+
+        if self.details && !self.mixed {
+            let strings = &self.strings;
+            self.mixed = self.single
+                .as_ref()
+                .map_or(false, |s| s.iter().any(|s| find_code(line, s, strings).is_some()));
+        }
+        let usafe = self.usafe && self.is_unsafe(line);
+        if let Some(ref mut h) = self.heredocs {
+            h.scan(line);
+        }
+        // Python's strings are tracked by `py_strings`, where an unclosed quote is an error
+        if self.open_block.is_none() && self.py_strings.is_none() {
+            self.open_string = unclosed_string(line, &self.strings, &self.blocks, &self.single);
+        }
+        self.has_safety_comment = false;
+        self.line(LineKind::Code, usafe)
+    }
+
+    // Whether the line of code `line` is unsafe, either inside an unsafe block or matching one of
+    // the risky patterns of the language
+    fn is_unsafe(&mut self, line: &str) -> bool {
+        debugln!("Calculating --unsafe-statistics");
+        let code = blank_literals(line, &self.strings);
+        let line = &*code;
+        if self.lang.unsafe_keyword().is_none() {
+            debugln!("There are risky patterns");
+            return match self.res.patterns.as_ref().and_then(|re| re.captures(line)) {
+                Some(caps) => {
+                    let before = caps.at(1).unwrap_or("");
+                    debugln!("It matched a risky pattern; usafe_line={:?}", line);
+                    !in_comment(&self.lang, before)
+                }
+                None => false,
+            };
+        }
+        debugln!("There is a keyword");
+        // Every block on the line needs a safety comment, even inside an unsafe function
+        if self.check_safety && !self.has_safety_comment && !line.contains(self.marker) {
+            let lang = &self.lang;
+            self.unjustified = self.res.block.as_ref().map_or(false, |re| {
+                re.find_iter(line).any(|(start, _)| !in_comment(lang, &line[..start]))
+            });
+            if self.unjustified {
+                debugln!("unsafe block without a safety comment");
+            }
+        }
+        let mut usafe = false;
+        if self.is_in_unsafe {
+            debugln!("It didn't contain the keyword, but we are still in unsafe");
+            usafe = true;
+            self.bracket_count = count_brackets(line, Some(self.bracket_count));
+            self.is_in_unsafe = self.bracket_count > 0;
+            debugln!("after counting brackets; is_in_unsafe={:?}; bracket_count={:?}",
+                     self.is_in_unsafe,
+                     self.bracket_count);
+        } else if let Some(caps) = self.res.keyword.captures(line) {
+            let should_count = caps.at(1).map_or(true, |before| !in_comment(&self.lang, before));
+            if should_count {
+                debugln!("It contained the keyword; usafe_line={:?}", line);
+                usafe = true;
+                if let Some(after) = caps.at(2) {
+                    debugln!("after_usafe={:?}", after);
+                    self.bracket_count = count_brackets(after, None);
+                    self.is_in_unsafe = self.bracket_count > 0;
+                    debugln!("after counting brackets; is_in_unsafe={:?}; bracket_count={:?}",
+                             self.is_in_unsafe,
+                             self.bracket_count);
+                }
+            }
+        } else {
+            debugln!("It didn't contain the keyword, and we are not in unsafe");
+        }
+
+        if self.bracket_count < 0 {
+            debugln!("bracket_count < 0; resetting");
+            self.bracket_count = 0
+        }
+        usafe
+    }
+}
+
+/// Whether the end of `before` is inside a comment, i.e. a match following it was commented out
+fn in_comment(lang: &Language, before: &str) -> bool {
+    let strings = lang.strings();
+    if let Some(single_v) = lang.single() {
+        if single_v.iter().any(|s| find_code(before, s, &strings).is_some()) {
+            return true;
+        }
+    }
+    if let Some((pos, block)) = find_block(before, &lang.multi(), &strings) {
+        return !before[pos + block.start.len()..].contains(&*block.end);
+    }
+    false
+}
+
+/// `src` with the comments of `lang` removed, keeping its lines and the code around them
+pub fn strip_comments(lang: &Language, src: &str) -> String {
+    let strings = lang.strings();
+    let single = lang.single().unwrap_or_default();
+    let blocks = lang.multi();
+    let mut open: Option<Block<String>> = None;
+    let mut ret = String::with_capacity(src.len());
+    for line in src.lines() {
+        let mut rest = line;
+        // Blocks which must start a line are made of whole lines, i.e. Ruby's `=begin` and `=end`
+        match open {
+            Some(ref block) if block.line_start => {
+                if line.starts_with(&*block.end) {
+                    open = None;
+                }
+                rest = "";
+            }
+            Some(_) => (),
+            None => {
+                if let Some(block) = blocks.iter()
+                    .find(|b| b.line_start && line.starts_with(&*b.start)) {
+                    open = Some(block.clone());
+                    rest = "";
+                }
+            }
+        }
+        loop {
+            if let Some(block) = open.clone() {
+                match rest.find(&*block.end) {
+                    Some(i) if !block.line_start => {
+                        rest = &rest[i + block.end.len()..];
+                        open = None;
+                    }
+                    _ => break,
+                }
+            }
+            let comment = single.iter().filter_map(|s| find_code(rest, s, &strings)).min();
+            match find_block(rest, &blocks, &strings) {
+                Some((pos, block)) if comment.map_or(true, |c| pos < c) => {
+                    ret.push_str(&rest[..pos]);
+                    rest = &rest[pos + block.start.len()..];
+                    open = Some(block);
+                }
+                _ => {
+                    ret.push_str(&rest[..comment.unwrap_or(rest.len())]);
+                    break;
+                }
+            }
+        }
+        ret.push('\n');
+    }
+    ret
+}
+
+// The first block comment opened in `line` outside of a string literal, and its position.
+// Blocks which must start a line are skipped, as they can't open in the middle of one.
+fn find_block(line: &str,
+              blocks: &[Block<String>],
+              strings: &[String])
+              -> Option<(usize, Block<String>)> {
+    blocks.iter()
+        .filter(|b| !b.line_start)
+        .filter_map(|b| find_code(line, &b.start, strings).map(|pos| (pos, b.clone())))
+        .min_by_key(|&(pos, _)| pos)
+}
+
+// Whether the comment opened by `start` at the beginning of `line` is a doc comment, i.e. `///`,
+// `//!`, `/**` or `(**`, but not a `////` rule or an empty `/**/`
+fn is_doc(line: &str, start: &str, end: Option<&str>) -> bool {
+    let rest = &line[start.len()..];
+    let last = match start.chars().next_back() {
+        Some(c) if start.len() > 1 => c,
+        _ => return false,
+    };
+    let mut chars = rest.chars();
+    match chars.next() {
+        Some('!') => true,
+        Some(c) if c == last => {
+            chars.next() != Some(last) && !end.map_or(false, |end| rest.starts_with(end))
+        }
+        _ => false,
+    }
+}
+
+// Whether `line`, which is inside the block comment `block`, closes it
+fn closes(block: &Block<String>, line: &str) -> bool {
+    if block.line_start {
+        line.starts_with(&*block.end)
+    } else {
+        line.contains(&*block.end)
+    }
+}
+
+/// Finds the first `pat` in `line` which isn't inside a string literal delimited by one of
+/// `strings`, or a character literal
+pub(crate) fn find_code(line: &str, pat: &str, strings: &[String]) -> Option<usize> {
+    if pat.is_empty() {
+        return None;
+    }
+    let mut i = 0;
+    while i < line.len() {
+        if line[i..].starts_with(pat) {
+            return Some(i);
+        }
+        i = match skip_literal(line, i, strings) {
+            Some(end) => end,
+            None => i + line[i..].chars().next().map_or(1, |c| c.len_utf8()),
+        };
+    }
+    None
+}
+
+/// `line` with its string and character literals replaced by spaces, so that keywords and brackets
+/// inside them are ignored while everything else keeps its position
+pub(crate) fn blank_literals(line: &str, strings: &[String]) -> String {
+    let mut ret = String::with_capacity(line.len());
+    let mut i = 0;
+    while i < line.len() {
+        match skip_literal(line, i, strings) {
+            Some(end) => {
+                ret.push_str(&" ".repeat(end - i));
+                i = end;
+            }
+            None => {
+                let c = line[i..].chars().next().unwrap_or(' ');
+                ret.push(c);
+                i += c.len_utf8();
+            }
+        }
+    }
+    ret
+}
+
+// If a string literal delimited by one of `strings`, or a character literal, starts at `i` in
+// `line`, the position just past its end, or the end of the line if it isn't closed on it
+fn skip_literal(line: &str, i: usize, strings: &[String]) -> Option<usize> {
+    let rest = &line[i..];
+    for delim in strings.iter().filter(|d| !d.is_empty()) {
+        if rest.starts_with(&**delim) {
+            return Some(close_string(line, i + delim.len(), delim).unwrap_or(line.len()));
+        }
+    }
+    // Character literals such as `'"'` and `'\''` in languages where `'` doesn't delimit strings,
+    // which aren't mistaken for Rust's lifetimes as those aren't closed by a quote
+    if !rest.starts_with('\'') {
+        return None;
+    }
+    let mut chars = rest.char_indices().skip(1);
+    match chars.next() {
+        Some((_, '\\')) => {
+            chars.next();
+            chars.take(10).find(|&(_, c)| c == '\'').map(|(end, _)| i + end + 1)
+        }
+        Some((_, c)) if c != '\'' => {
+            match chars.next() {
+                Some((end, '\'')) => Some(i + end + 1),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+// The position just past the end of the string literal delimited by `delim` whose contents start
+// at `from` in `line`, ignoring escaped delimiters, if it's closed on the line
+fn close_string(line: &str, from: usize, delim: &str) -> Option<usize> {
+    let mut j = from;
+    while j < line.len() {
+        if line[j..].starts_with('\\') {
+            j += 1;
+        } else if line[j..].starts_with(delim) {
+            return Some(j + delim.len());
+        }
+        j += line[j..].chars().next().map_or(1, |c| c.len_utf8());
+    }
+    None
+}
+
+// The delimiter of a string literal opened in the code of `line` but not closed on it, which
+// continues on the next line
+fn unclosed_string(line: &str,
+                   strings: &[String],
+                   blocks: &[Block<String>],
+                   single: &Option<Vec<String>>)
+                   -> Option<String> {
+    let comment = single.iter()
+        .flat_map(|v| v.iter())
+        .filter_map(|s| find_code(line, s, strings))
+        .chain(find_block(line, blocks, strings).map(|(pos, _)| pos))
+        .min();
+    let code = &line[..comment.unwrap_or(line.len())];
+    let mut i = 0;
+    while i < code.len() {
+        let opened = strings.iter().find(|d| !d.is_empty() && code[i..].starts_with(&***d));
+        if let Some(delim) = opened {
+            match close_string(code, i + delim.len(), delim) {
+                Some(end) => i = end,
+                None => return Some(delim.clone()),
+            }
+            continue;
+        }
+        i = match skip_literal(code, i, strings) {
+            Some(end) => end,
+            None => i + code[i..].chars().next().map_or(1, |c| c.len_utf8()),
+        };
+    }
+    None
+}
+
+fn count_brackets(line: &str, count: Option<i64>) -> i64 {
+    let mut b: i64 = count.unwrap_or(0);
+    for c in line.chars() {
+        match c {
+            '{' => b += 1,
+            '}' => b -= 1,
+            _ => (),
+        }
+    }
+    b
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{blank_literals, find_code, LineCounter, UnsafeRes};
+    use config::Config;
+    use language::Language;
+
+    // The numbers of the lines of `src` reported as opening an unjustified unsafe block
+    fn unjustified(src: &str) -> Vec<usize> {
+        let cfg = Config { check_safety: true, ..Config::default() };
+        let res = UnsafeRes::new(&Language::Rust);
+        let mut counter = LineCounter::new(&cfg, Language::Rust, &res);
+        src.lines()
+            .enumerate()
+            .filter(|&(_, line)| {
+                counter.classify(line);
+                counter.is_unjustified()
+            })
+            .map(|(n, _)| n + 1)
+            .collect()
+    }
+
+    #[test]
+    fn empty_delimiters_are_skipped() {
+        let strings = vec!["".to_owned(), "\"".to_owned()];
+        assert_eq!(find_code("a \"//\" // b", "//", &strings), Some(7));
+        assert_eq!(find_code("a // b", "", &strings), None);
+        assert_eq!(blank_literals("x = \"y\";", &strings), "x =    ;");
+    }
+
+    #[test]
+    fn nested_unsafe_blocks_are_checked() {
+        let src = "unsafe fn f() {\n    let a = unsafe { g() };\n    // SAFETY: checked above\n    \
+                   let b = unsafe { h() };\n}\nunsafe impl Send for S {\n    fn s() { unsafe { \
+                   i() } }\n}";
+        assert_eq!(unjustified(src), vec![2, 7]);
+    }
+
+    #[test]
+    fn every_unsafe_block_on_a_line_is_checked() {
+        assert_eq!(unjustified("unsafe fn f() {} let b = unsafe { y() };"), vec![1]);
+        assert_eq!(unjustified("let s = \"unsafe {\"; let b = unsafe { y() };"), vec![1]);
+        assert!(unjustified("let s = \"unsafe {\"; // unsafe {").is_empty());
+    }
+
+    #[test]
+    fn multi_line_strings_are_not_code() {
+        let src = "let s = \"a\n    unsafe { b }\n    c\";\nlet d = unsafe { e() };";
+        assert_eq!(unjustified(src), vec![4]);
+        let cfg = Config { usafe: true, ..Config::default() };
+        let res = UnsafeRes::new(&Language::Rust);
+        let mut counter = LineCounter::new(&cfg, Language::Rust, &res);
+        let usafe: Vec<_> = src.lines().map(|l| counter.classify(l).usafe).collect();
+        assert_eq!(usafe, vec![false, false, false, true]);
+    }
+}
@@ -1,27 +1,65 @@
 mod counts;
+mod dirs;
+mod explain;
+mod group;
+pub(crate) mod line;
+mod policy;
+mod report;
 
 
 use fmt;
 use language::Language;
 pub use self::counts::Counts;
+pub use self::dirs::Dir;
+pub use self::explain::explain;
+pub use self::group::Group;
+pub use self::line::{Line, LineKind};
+pub use self::policy::UnsafePolicy;
 
+use std::cmp;
+use std::collections::BTreeMap;
+use std::env;
 use std::fmt as StdFmt;
 use std::ops::Deref;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
+/// The totals of the files of a single language
 #[derive(Debug)]
 pub struct Count {
+    /// The language of the files
     pub lang: Language,
+    /// The files counted
     pub files: Vec<PathBuf>,
+    /// The lines of code, which includes lines with both code and a comment
     pub code: u64,
+    /// The lines holding only comments
     pub comments: u64,
+    /// The empty lines, or those holding only whitespace
     pub blanks: u64,
+    /// All lines
     pub lines: u64,
+    /// The lines of code which are unsafe, if unsafe statistics were requested
     pub usafe: u64,
+    /// The bytes of the lines, not including line endings
+    pub bytes: u64,
+    /// The characters of the lines, not including line endings
+    pub chars: u64,
+    /// The characters in the longest line
+    pub longest_line: u64,
+    /// The crate roots among the files, and how each treats the `unsafe_code` lint
+    pub crate_roots: Vec<(PathBuf, UnsafePolicy)>,
+    /// The totals of each file, including those of any regions of other files merged into this
+    /// count
+    pub file_counts: Vec<FileCount>,
+    /// Counts of the regions of these files written in other languages, i.e. the `<script>`
+    /// blocks of HTML files
+    pub embedded: Vec<Count>,
+    /// The thousands separator used when displaying the totals
     pub sep: Option<char>,
 }
 
 impl Count {
+    /// An empty count of `lang`
     pub fn new(lang: Language, sep: Option<char>) -> Self {
         Count {
             lang: lang,
@@ -31,37 +69,217 @@ impl Count {
             blanks: 0,
             lines: 0,
             usafe: 0,
+            bytes: 0,
+            chars: 0,
+            longest_line: 0,
+            crate_roots: vec![],
+            file_counts: vec![],
+            embedded: vec![],
             sep: sep,
         }
     }
 
+    /// Adds a file to be counted
     pub fn add_file(&mut self, f: PathBuf) {
         self.files.push(f);
     }
 
+    /// Adds the lines counted by `other`, including the totals of each of its files, and those of
+    /// its files which aren't among these already, i.e. the HTML files holding the embedded
+    /// JavaScript of `other`
+    pub fn merge(&mut self, other: &Count) {
+        for f in &other.files {
+            if !self.files.contains(f) {
+                self.files.push(f.clone());
+            }
+        }
+        self.code += other.code;
+        self.comments += other.comments;
+        self.blanks += other.blanks;
+        self.lines += other.lines;
+        self.usafe += other.usafe;
+        self.bytes += other.bytes;
+        self.chars += other.chars;
+        self.longest_line = cmp::max(self.longest_line, other.longest_line);
+        self.file_counts.extend(other.file_counts.iter().cloned());
+    }
+
+    /// The number of lines, formatted with the thousands separator
     pub fn lines(&self) -> String {
         fmt::format_number(self.lines, self.sep)
     }
 
+    /// The number of lines of code, formatted with the thousands separator
     pub fn code(&self) -> String {
         fmt::format_number(self.code, self.sep)
     }
 
+    /// The number of blank lines, formatted with the thousands separator
     pub fn blanks(&self) -> String {
         fmt::format_number(self.blanks, self.sep)
     }
 
+    /// The number of unsafe lines, formatted with the thousands separator
     pub fn usafe(&self) -> String {
         fmt::format_number(self.usafe, self.sep)
     }
 
+    /// The number of comment lines, formatted with the thousands separator
     pub fn comments(&self) -> String {
         fmt::format_number(self.comments, self.sep)
     }
 
+    /// A summary of the `unsafe_code` lint level of each crate root, i.e. `1 forbid, 2 allow`
+    pub fn unsafe_policies(&self) -> String {
+        let mut ret = vec![];
+        for policy in &[UnsafePolicy::Forbid, UnsafePolicy::Deny, UnsafePolicy::Allow] {
+            let n = self.crate_roots.iter().filter(|&&(_, p)| p == *policy).count();
+            if n > 0 {
+                ret.push(format!("{} {}", fmt::format_number(n as u64, self.sep), policy.name()));
+            }
+        }
+        ret.join(", ")
+    }
+
+    /// The number of files, formatted with the thousands separator
     pub fn total_files(&self) -> String {
         fmt::format_number(self.files.len() as u64, self.sep)
     }
+
+    /// The values derived from the totals
+    pub fn metrics(&self) -> Metrics {
+        Metrics::new(self.files.len() as u64,
+                     self.lines,
+                     self.blanks,
+                     self.comments,
+                     self.code,
+                     self.usafe)
+    }
+}
+
+/// Values derived from the totals of a row of the results, which are `None` where they would be
+/// divided by zero
+#[derive(Debug, Copy, Clone)]
+pub struct Metrics {
+    /// The lines of comments for each line of code
+    pub comment_ratio: Option<f64>,
+    /// The fraction of lines which are blank
+    pub blank_ratio: Option<f64>,
+    /// The average lines in each file
+    pub lines_per_file: Option<f64>,
+    /// The unsafe lines in each thousand lines of code
+    pub unsafe_density: Option<f64>,
+}
+
+impl Metrics {
+    /// The metrics of `files` files holding the given lines
+    pub fn new(files: u64, lines: u64, blanks: u64, comments: u64, code: u64, usafe: u64) -> Self {
+        let ratio = |n: u64, d: u64| if d == 0 { None } else { Some(n as f64 / d as f64) };
+        Metrics {
+            comment_ratio: ratio(comments, code),
+            blank_ratio: ratio(blanks, lines),
+            lines_per_file: ratio(lines, files),
+            unsafe_density: ratio(usafe * 1000, code),
+        }
+    }
+}
+
+/// The totals of a single file, or of its lines in one language if others are embedded in it
+#[derive(Debug, Clone)]
+pub struct FileCount {
+    /// The path of the file
+    pub path: PathBuf,
+    /// The lines of code, which includes lines with both code and a comment
+    pub code: u64,
+    /// The lines holding only comments
+    pub comments: u64,
+    /// The empty lines, or those holding only whitespace
+    pub blanks: u64,
+    /// All lines
+    pub lines: u64,
+    /// The lines of code which are unsafe, if unsafe statistics were requested
+    pub usafe: u64,
+    /// The bytes of the lines, not including line endings
+    pub bytes: u64,
+    /// The characters of the lines, not including line endings
+    pub chars: u64,
+    /// The characters in the longest line
+    pub longest_line: u64,
+}
+
+impl FileCount {
+    /// An empty count of the file at `path`
+    pub fn new(path: PathBuf) -> Self {
+        FileCount {
+            path: path,
+            code: 0,
+            comments: 0,
+            blanks: 0,
+            lines: 0,
+            usafe: 0,
+            bytes: 0,
+            chars: 0,
+            longest_line: 0,
+        }
+    }
+
+    /// Adds the lines counted by `other`
+    pub fn merge(&mut self, other: &FileCount) {
+        self.code += other.code;
+        self.comments += other.comments;
+        self.blanks += other.blanks;
+        self.lines += other.lines;
+        self.usafe += other.usafe;
+        self.bytes += other.bytes;
+        self.chars += other.chars;
+        self.longest_line = cmp::max(self.longest_line, other.longest_line);
+    }
+}
+
+// The totals of each file of `counts`, including any code embedded in it, in order of their paths
+fn by_file(counts: &[Count]) -> BTreeMap<&Path, FileCount> {
+    let mut files: BTreeMap<&Path, FileCount> = BTreeMap::new();
+    for count in counts.iter().chain(counts.iter().flat_map(|c| c.embedded.iter())) {
+        for fc in &count.file_counts {
+            files.entry(&fc.path)
+                .or_insert_with(|| FileCount::new(fc.path.clone()))
+                .merge(fc);
+        }
+    }
+    files
+}
+
+// The directory `dir` relative to the current directory if it's inside it, where `.` is the
+// current directory itself
+fn relative_dir(dir: &Path) -> PathBuf {
+    let cwd = env::current_dir().unwrap_or_default();
+    match dir.strip_prefix(&cwd) {
+        Ok(rel) if rel == Path::new("") => PathBuf::from("."),
+        Ok(rel) => rel.to_path_buf(),
+        Err(..) if dir == Path::new("") => PathBuf::from("."),
+        Err(..) => dir.to_path_buf(),
+    }
+}
+
+/// The counts of a single source, such as an in-memory buffer, and how each of its lines was
+/// counted
+#[derive(Debug)]
+pub struct SourceCount {
+    count: Count,
+    classified: Vec<Line>,
+}
+
+impl SourceCount {
+    /// The totals of the source, with those of any code embedded in it in another language in
+    /// `embedded`
+    pub fn count(&self) -> &Count {
+        &self.count
+    }
+
+    /// How each line was counted, in order
+    pub fn classified(&self) -> &[Line] {
+        &self.classified
+    }
 }
 
 impl Deref for Count {
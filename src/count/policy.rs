@@ -0,0 +1,165 @@
+use count::line;
+use language::Language;
+use std::path::Path;
+
+/// How a crate root treats the `unsafe_code` lint
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum UnsafePolicy {
+    /// `#![forbid(unsafe_code)]`, which can't be overridden
+    Forbid,
+    /// `#![deny(unsafe_code)]`, which may be allowed again for single items
+    Deny,
+    /// Neither, so unsafe code is allowed
+    Allow,
+}
+
+impl UnsafePolicy {
+    /// Finds the strictest `#![forbid(..)]` or `#![deny(..)]` crate attribute naming
+    /// `unsafe_code` in the source of a crate root, including ones inside `cfg_attr`, but not
+    /// those which are commented out
+    pub fn from_source(src: &str) -> UnsafePolicy {
+        let src = line::strip_comments(&Language::Rust, src);
+        let mut policy = UnsafePolicy::Allow;
+        let mut rest = &*src;
+        while let Some(i) = rest.find("#!") {
+            rest = rest[i + 2..].trim_left();
+            if !rest.starts_with('[') {
+                continue;
+            }
+            let attr = match closing(&rest[1..], ']') {
+                Some(end) => &rest[1..end + 1],
+                None => break,
+            };
+            match level(attr) {
+                Some(UnsafePolicy::Forbid) => return UnsafePolicy::Forbid,
+                Some(level) => policy = level,
+                None => (),
+            }
+        }
+        policy
+    }
+
+    /// Whether `path` is the root of a crate by Cargo's target conventions (`lib.rs`, `main.rs`,
+    /// `build.rs`, or a file directly inside `bin/`, `examples/`, `tests/` or `benches/`)
+    pub fn is_crate_root(path: &Path) -> bool {
+        if path.extension().map_or(true, |e| e != "rs") {
+            return false;
+        }
+        match path.file_name().and_then(|f| f.to_str()) {
+            Some("lib.rs") | Some("main.rs") | Some("build.rs") => return true,
+            _ => (),
+        }
+        match path.parent().and_then(|p| p.file_name()).and_then(|f| f.to_str()) {
+            Some("bin") | Some("examples") | Some("tests") | Some("benches") => true,
+            _ => false,
+        }
+    }
+
+    /// The name of the lint level, i.e. `forbid`
+    pub fn name(&self) -> &'static str {
+        match *self {
+            UnsafePolicy::Forbid => "forbid",
+            UnsafePolicy::Deny => "deny",
+            UnsafePolicy::Allow => "allow",
+        }
+    }
+}
+
+// The level an attribute, i.e. `deny(unsafe_code)` or `cfg_attr(test, forbid(unsafe_code))`,
+// sets the `unsafe_code` lint to, if any
+fn level(attr: &str) -> Option<UnsafePolicy> {
+    let attr = attr.trim();
+    let open = match attr.find('(') {
+        Some(open) if attr.ends_with(')') &&
+                      closing(&attr[open + 1..], ')') == Some(attr.len() - open - 2) => open,
+        _ => return None,
+    };
+    let args = split_args(&attr[open + 1..attr.len() - 1]);
+    match attr[..open].trim() {
+        "forbid" if args.contains(&"unsafe_code") => Some(UnsafePolicy::Forbid),
+        "deny" if args.contains(&"unsafe_code") => Some(UnsafePolicy::Deny),
+        // The predicate is followed by any number of attributes
+        "cfg_attr" => {
+            let levels: Vec<_> = args.iter().skip(1).filter_map(|a| level(a)).collect();
+            if levels.contains(&UnsafePolicy::Forbid) {
+                Some(UnsafePolicy::Forbid)
+            } else {
+                levels.into_iter().next()
+            }
+        }
+        _ => None,
+    }
+}
+
+// The position of the `close` bracket ending the brackets opened just before `s`, skipping
+// nested brackets and string literals
+fn closing(s: &str, close: char) -> Option<usize> {
+    let mut depth = 0;
+    let mut in_str = false;
+    let mut escaped = false;
+    for (i, c) in s.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_str => escaped = true,
+            '"' => in_str = !in_str,
+            _ if in_str => (),
+            '(' | '[' | '{' => depth += 1,
+            c if c == close && depth == 0 => return Some(i),
+            ')' | ']' | '}' => depth -= 1,
+            _ => (),
+        }
+    }
+    None
+}
+
+// The arguments of an attribute, split on the commas outside of any nested brackets
+fn split_args(s: &str) -> Vec<&str> {
+    let mut ret = vec![];
+    let mut rest = s;
+    while !rest.trim().is_empty() {
+        let end = closing(rest, ',').unwrap_or(rest.len());
+        ret.push(rest[..end].trim());
+        rest = if end < rest.len() { &rest[end + 1..] } else { "" };
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{split_args, UnsafePolicy};
+    use std::path::Path;
+
+    #[test]
+    fn from_source() {
+        let policy = UnsafePolicy::from_source;
+        assert_eq!(policy("#![forbid(unsafe_code)]\nfn main() {}"), UnsafePolicy::Forbid);
+        assert_eq!(policy("#![deny(missing_docs, unsafe_code)]"), UnsafePolicy::Deny);
+        assert_eq!(policy("#! [ deny( unsafe_code ) ]"), UnsafePolicy::Deny);
+        assert_eq!(policy("#![deny(unsafe_code)]\n#![cfg_attr(not(test), forbid(unsafe_code))]"),
+                   UnsafePolicy::Forbid);
+        assert_eq!(policy("#![cfg_attr(feature = \"a\", allow(dead_code), deny(unsafe_code))]"),
+                   UnsafePolicy::Deny);
+        assert_eq!(policy("#![allow(unsafe_code)]\n#![doc = \"deny(unsafe_code)\"]"),
+                   UnsafePolicy::Allow);
+        assert_eq!(policy("// #![forbid(unsafe_code)]\n/* #![deny(unsafe_code)] */"),
+                   UnsafePolicy::Allow);
+        assert_eq!(policy("#![forbid(unsafe_code_extra)]"), UnsafePolicy::Allow);
+        assert_eq!(policy("#![forbid(unsafe_code"), UnsafePolicy::Allow);
+    }
+
+    #[test]
+    fn args() {
+        assert_eq!(split_args("a, b(c, d), \"e,f\""), vec!["a", "b(c, d)", "\"e,f\""]);
+        assert_eq!(split_args(" "), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn crate_roots() {
+        for root in &["src/lib.rs", "src/main.rs", "build.rs", "src/bin/a.rs", "tests/t.rs"] {
+            assert!(UnsafePolicy::is_crate_root(Path::new(root)), "{}", root);
+        }
+        for other in &["src/a.rs", "src/bin/a/util.rs", "tests/lib.py"] {
+            assert!(!UnsafePolicy::is_crate_root(Path::new(other)), "{}", other);
+        }
+    }
+}
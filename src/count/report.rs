@@ -0,0 +1,805 @@
+use config::{Config, GroupBy, Metric, OutputFormat};
+use count::{Count, Counts, Dir, FileCount, Group, Metrics};
+use error::{CliError, CliResult};
+use fmt::{self, Format};
+use language::Language;
+
+use std::io::{self, Write};
+
+use tabwriter::TabWriter;
+
+// A cell of a table, with the number it displays so that HTML tables can be sorted by it
+struct Cell {
+    text: String,
+    value: Option<f64>,
+}
+
+impl Cell {
+    fn text<S: Into<String>>(text: S) -> Self {
+        Cell {
+            text: text.into(),
+            value: None,
+        }
+    }
+
+    fn number(n: u64, sep: Option<char>) -> Self {
+        Cell {
+            text: fmt::format_number(n, sep),
+            value: Some(n as f64),
+        }
+    }
+
+    // A value shown with `precision` decimals, or an empty cell if there isn't one
+    fn float(v: Option<f64>, precision: usize) -> Self {
+        Cell {
+            text: v.map_or("".to_owned(), |v| format!("{:.*}", precision, v)),
+            value: v,
+        }
+    }
+}
+
+// A row of a table, indented `level` levels below the row it belongs to, along with its lines of
+// code, comments and blanks for charting
+struct Row {
+    level: usize,
+    cells: Vec<Cell>,
+    lines: [u64; 3],
+}
+
+// A table of totals whose first column names each row, with an optional row of the totals of all
+// of them
+struct Table {
+    headings: Vec<&'static str>,
+    rows: Vec<Row>,
+    totals: Option<Row>,
+}
+
+impl Table {
+    // The totals of each language or group, as the text output has always displayed them
+    fn results(cfg: &Config, counts: &Counts) -> Self {
+        let sep = cfg.thousands;
+        // The `unsafe_code` lint levels are only known for each language
+        let by_lang = cfg.group_by == GroupBy::Language;
+        let policies = cfg.usafe && by_lang;
+        let mut headings = vec![cfg.group_by.heading(), "Files"];
+        headings.extend(line_headings(cfg));
+        if policies {
+            headings.push("Unsafe Code");
+        }
+        headings.extend(extra_headings(cfg));
+
+        let row = |level: usize, name: String, g: &Group, policy: Option<String>| {
+            let mut cells = vec![Cell::text(name), Cell::number(g.files, sep)];
+            cells.extend(line_cells(cfg, g));
+            cells.extend(policy.map(Cell::text));
+            cells.extend(extra_cells(cfg, g));
+            Row {
+                level: level,
+                cells: cells,
+                lines: [g.code, g.comments, g.blanks],
+            }
+        };
+        let mut rows = vec![];
+        if by_lang {
+            for count in counts.counts() {
+                let policy = if policies { Some(count.unsafe_policies()) } else { None };
+                rows.push(row(0, count.lang.name().to_owned(), &Group::from(count), policy));
+                for e in &count.embedded {
+                    let policy = if policies { Some(e.unsafe_policies()) } else { None };
+                    rows.push(row(1,
+                                  format!("{} (embedded)", e.lang.name()),
+                                  &Group::from(e),
+                                  policy));
+                }
+            }
+        } else {
+            for g in counts.groups() {
+                rows.push(row(0, g.name.clone(), &g, None));
+            }
+        }
+
+        let t = counts.totals();
+        let policy = if policies { Some("".to_owned()) } else { None };
+        let mut totals = row(0, "Totals".to_owned(), &t, policy);
+        if let Some(i) = headings.iter().position(|&h| h == UNSAFE_HEADING) {
+            // Unlike the other rows, the totals always show the percentage of unsafe code, unless
+            // there's no code to take it of
+            totals.cells[i] = Cell {
+                text: if t.code == 0 {
+                    fmt::format_number(t.usafe, sep)
+                } else {
+                    format!("{} ({:.2}%)",
+                            fmt::format_number(t.usafe, sep),
+                            (t.usafe as f64 / t.code as f64) * 100.00f64)
+                },
+                value: Some(t.usafe as f64),
+            };
+        }
+        Table {
+            headings: headings,
+            rows: rows,
+            totals: Some(totals),
+        }
+    }
+
+    // The totals of each directory in `tree`, each indented below its parent
+    fn dirs(cfg: &Config, tree: &Dir) -> Self {
+        let mut headings = vec!["Directory", "Files"];
+        headings.extend(line_headings(cfg));
+        headings.extend(extra_headings(cfg));
+        let mut table = Table {
+            headings: headings,
+            rows: vec![],
+            totals: None,
+        };
+        table.add_dir(cfg, tree, 0);
+        table
+    }
+
+    fn add_dir(&mut self, cfg: &Config, dir: &Dir, level: usize) {
+        let t = &dir.totals;
+        // The root is named by its path and the rest by their names, all ending in a `/`
+        let name = if level == 0 {
+            t.path.to_string_lossy()
+        } else {
+            t.path.file_name().map_or(t.path.to_string_lossy(), |n| n.to_string_lossy())
+        };
+        let name = if name.ends_with('/') {
+            name.into_owned()
+        } else {
+            format!("{}/", name)
+        };
+        let g = file_group(name, dir.files, t);
+        let mut cells = vec![Cell::text(g.name.clone()), Cell::number(g.files, cfg.thousands)];
+        cells.extend(line_cells(cfg, &g));
+        cells.extend(extra_cells(cfg, &g));
+        self.rows.push(Row {
+            level: level,
+            cells: cells,
+            lines: [g.code, g.comments, g.blanks],
+        });
+        for sub in &dir.dirs {
+            self.add_dir(cfg, sub, level + 1);
+        }
+    }
+
+    // The totals of each file in each of its languages
+    fn files(cfg: &Config, counts: &Counts) -> Self {
+        let mut headings = vec!["File", "Language"];
+        headings.extend(line_headings(cfg));
+        headings.extend(extra_headings(cfg));
+        let rows = counts.files()
+            .into_iter()
+            .map(|(lang, fc)| {
+                let g = file_group(fc.path.display().to_string(), 1, fc);
+                let mut cells = vec![Cell::text(g.name.clone()), Cell::text(lang.name())];
+                cells.extend(line_cells(cfg, &g));
+                cells.extend(extra_cells(cfg, &g));
+                Row {
+                    level: 0,
+                    cells: cells,
+                    lines: [g.code, g.comments, g.blanks],
+                }
+            })
+            .collect();
+        Table {
+            headings: headings,
+            rows: rows,
+            totals: None,
+        }
+    }
+
+    // The files skipped because their language wasn't recognized, by extension
+    fn skipped(cfg: &Config, counts: &Counts) -> Self {
+        let rows = counts.skipped_by_ext()
+            .into_iter()
+            .map(|(ext, n, size)| {
+                Row {
+                    level: 0,
+                    cells: vec![Cell::text(ext),
+                                Cell::number(n, cfg.thousands),
+                                Cell::number(size, cfg.thousands)],
+                    lines: [0, 0, 0],
+                }
+            })
+            .collect();
+        Table {
+            headings: vec!["Extension", "Files", "Bytes"],
+            rows: rows,
+            totals: None,
+        }
+    }
+
+    // Whether the `i`th column holds numbers, which are aligned to the right
+    fn is_numeric(&self, i: usize) -> bool {
+        self.rows.iter().chain(self.totals.iter()).any(|r| r.cells[i].value.is_some())
+    }
+
+    fn text(&self) -> CliResult<String> {
+        let mut w = TabWriter::new(vec![]);
+        let rule = format!("\t{}\n",
+                           self.headings
+                               .iter()
+                               .map(|h| "-".repeat(h.len()))
+                               .collect::<Vec<_>>()
+                               .join("\t"));
+        cli_try!(write!(w, "\t{}\n", self.headings.join("\t")));
+        cli_try!(write!(w, "{}", rule));
+        for row in &self.rows {
+            cli_try!(write!(w,
+                            "\t{}{}\n",
+                            "  ".repeat(row.level),
+                            row.cells.iter().map(|c| &*c.text).collect::<Vec<_>>().join("\t")));
+        }
+        if let Some(ref totals) = self.totals {
+            // The first column is left empty, and so are any trailing ones
+            let end = totals.cells.iter().rposition(|c| !c.text.is_empty()).map_or(1, |i| i + 1);
+            cli_try!(write!(w, "{}", rule));
+            cli_try!(write!(w,
+                            "Totals:\t\t{}\n",
+                            totals.cells[1..end]
+                                .iter()
+                                .map(|c| &*c.text)
+                                .collect::<Vec<_>>()
+                                .join("\t")));
+        }
+        cli_try!(w.flush());
+        Ok(String::from_utf8(w.unwrap()).ok().expect("failed to get valid UTF-8 String"))
+    }
+
+    fn markdown(&self) -> String {
+        let line = |cells: Vec<String>| format!("| {} |\n", cells.join(" | "));
+        let mut ret = line(self.headings.iter().map(|h| md_escape(h)).collect());
+        ret.push_str(&line((0..self.headings.len())
+            .map(|i| if self.is_numeric(i) { "---:" } else { ":---" }.to_owned())
+            .collect()));
+        for row in &self.rows {
+            ret.push_str(&line(row.cells
+                .iter()
+                .enumerate()
+                .map(|(i, c)| {
+                    let indent = if i == 0 { "&emsp;".repeat(row.level) } else { "".to_owned() };
+                    indent + &md_escape(&c.text)
+                })
+                .collect()));
+        }
+        if let Some(ref totals) = self.totals {
+            ret.push_str(&line(totals.cells
+                .iter()
+                .map(|c| if c.text.is_empty() {
+                    "".to_owned()
+                } else {
+                    format!("**{}**", md_escape(&c.text))
+                })
+                .collect()));
+        }
+        ret
+    }
+
+    // The table as HTML, which can be sorted by clicking its headings unless it's a tree
+    fn html(&self, sortable: bool) -> String {
+        let numeric: Vec<bool> = (0..self.headings.len()).map(|i| self.is_numeric(i)).collect();
+        let cells = |row: &Row| {
+            row.cells
+                .iter()
+                .enumerate()
+                .map(|(i, c)| {
+                    format!("<td{}{}{}>{}</td>",
+                            if numeric[i] { " class=\"n\"" } else { "" },
+                            c.value.map_or("".to_owned(), |v| format!(" data-value=\"{}\"", v)),
+                            if i == 0 && row.level > 0 {
+                                format!(" style=\"padding-left:{}em\"", row.level + 1)
+                            } else {
+                                "".to_owned()
+                            },
+                            fmt::html_escape(&c.text))
+                })
+                .collect::<String>()
+        };
+        let mut ret = format!("<table{}>\n<thead><tr>{}</tr></thead>\n<tbody>\n",
+                              if sortable { " class=\"sortable\"" } else { "" },
+                              self.headings
+                                  .iter()
+                                  .enumerate()
+                                  .map(|(i, h)| {
+                                      format!("<th{}>{}</th>",
+                                              if numeric[i] { " class=\"n\"" } else { "" },
+                                              fmt::html_escape(h))
+                                  })
+                                  .collect::<String>());
+        for r in &self.rows {
+            ret.push_str(&format!("<tr>{}</tr>\n", cells(r)));
+        }
+        ret.push_str("</tbody>\n");
+        if let Some(ref totals) = self.totals {
+            ret.push_str(&format!("<tfoot><tr>{}</tr></tfoot>\n", cells(totals)));
+        }
+        ret.push_str("</table>\n");
+        ret
+    }
+
+    // A bar of the code, comments and blanks of each row, scaled to the row with the most lines
+    fn html_chart(&self) -> String {
+        let max = self.rows.iter().map(|r| r.lines.iter().sum::<u64>()).max().unwrap_or(0);
+        let mut ret = "<table class=\"chart\">\n".to_owned();
+        for r in &self.rows {
+            let lines: u64 = r.lines.iter().sum();
+            let bars = ["code", "comments", "blanks"]
+                .iter()
+                .zip(r.lines.iter())
+                .map(|(class, &n)| {
+                    format!("<span class=\"{}\" style=\"flex-grow:{}\" title=\"{}: {}\"></span>",
+                            class,
+                            n,
+                            class,
+                            n)
+                })
+                .collect::<String>();
+            ret.push_str(&format!("<tr><td{}>{}</td><td class=\"n\">{}</td><td class=\"bars\">\
+                                   <div class=\"bar\" style=\"width:{:.1}%\">{}</div></td></tr>\n",
+                                  if r.level > 0 {
+                                      format!(" style=\"padding-left:{}em\"", r.level + 1)
+                                  } else {
+                                      "".to_owned()
+                                  },
+                                  fmt::html_escape(&r.cells[0].text),
+                                  r.cells
+                                      .iter()
+                                      .zip(self.headings.iter())
+                                      .find(|&(_, h)| *h == "Lines")
+                                      .map_or("".to_owned(), |(c, _)| c.text.clone()),
+                                  if max == 0 { 0f64 } else { lines as f64 * 100f64 / max as f64 },
+                                  bars));
+        }
+        ret.push_str("</table>\n<p class=\"legend\"><span class=\"code\"></span> code \
+                      <span class=\"comments\"></span> comments \
+                      <span class=\"blanks\"></span> blanks</p>\n");
+        ret
+    }
+}
+
+static HTML_STYLE: &'static str = "
+body { font-family: sans-serif; margin: 2em; color: #222; }
+table { border-collapse: collapse; margin-bottom: 2em; }
+th, td { padding: 0.3em 0.8em; border-bottom: 1px solid #ddd; text-align: left; }
+th { background: #f4f4f4; }
+.n { text-align: right; }
+table.sortable th { cursor: pointer; }
+tfoot td { font-weight: bold; border-top: 2px solid #888; }
+td.bars { width: 30em; }
+.bar { display: flex; height: 1em; }
+.code { background: #4c72b0; }
+.comments { background: #55a868; }
+.blanks { background: #ccc; }
+.legend span { display: inline-block; width: 1em; height: 1em; vertical-align: middle; }
+";
+
+// Sorts the body of a sortable table by the column whose heading is clicked, by the numbers of
+// its cells if it has them, and in the other direction when clicked again
+static HTML_SCRIPT: &'static str = "
+Array.prototype.forEach.call(document.querySelectorAll('table.sortable'), function (table) {
+  Array.prototype.forEach.call(table.tHead.rows[0].cells, function (th, i) {
+    th.addEventListener('click', function () {
+      var body = table.tBodies[0];
+      var rows = Array.prototype.slice.call(body.rows);
+      var asc = th.getAttribute('data-order') !== 'asc';
+      th.setAttribute('data-order', asc ? 'asc' : 'desc');
+      var value = function (row) {
+        var v = row.cells[i].getAttribute('data-value');
+        return v === null ? -Infinity : parseFloat(v);
+      };
+      rows.sort(function (a, b) {
+        var ord = th.className === 'n'
+          ? (value(a) === value(b) ? 0 : value(a) < value(b) ? -1 : 1)
+          : a.cells[i].textContent.localeCompare(b.cells[i].textContent);
+        return asc ? ord : -ord;
+      });
+      rows.forEach(function (row) { body.appendChild(row); });
+    });
+  });
+});
+";
+
+/// Writes the results to stdout as text, JSON, Markdown or HTML
+pub fn write(cfg: &Config, counts: &Counts) -> CliResult<()> {
+    match cfg.output {
+        OutputFormat::Text => write_text(cfg, counts),
+        OutputFormat::Json => write_json(cfg, counts),
+        OutputFormat::Markdown => write_markdown(cfg, counts),
+        OutputFormat::Html => write_html(cfg, counts),
+    }
+}
+
+fn write_text(cfg: &Config, counts: &Counts) -> CliResult<()> {
+    let results = try!(Table::results(cfg, counts).text());
+
+    verboseln!(cfg, "{} {}", Format::Good("Displaying"), "the results:");
+    if cfg.usafe {
+        for count in counts.counts() {
+            for &(ref root, policy) in &count.crate_roots {
+                verboseln!(cfg,
+                           "{} {} is #![{}(unsafe_code)]",
+                           Format::Good("Crate root"),
+                           root.display(),
+                           policy.name());
+            }
+        }
+    }
+    if counts.total_files() > 0 {
+        write!(io::stdout(), "{}", results).expect("failed to write output");
+        if let Some(depth) = cfg.by_dir {
+            println!("\n{}", try!(Table::dirs(cfg, &counts.dirs(depth)).text()));
+        }
+        if cfg.per_file {
+            println!("\n{}", try!(Table::files(cfg, counts).text()));
+        }
+    } else {
+        println!("\n\tNo source files were found matching the specified criteria");
+    }
+    if (cfg.skipped || cfg.verbose) && !counts.skipped().is_empty() {
+        println!("\n{} {} files with an unrecognized language:\n{}",
+                 Format::Warning("Skipped"),
+                 fmt::format_number(counts.skipped().len() as u64, cfg.thousands),
+                 try!(Table::skipped(cfg, counts).text()));
+        if cfg.list_skipped {
+            for &(ref file, _) in counts.skipped() {
+                println!("\t{}", file.display());
+            }
+        }
+    }
+    Ok(())
+}
+
+fn write_markdown(cfg: &Config, counts: &Counts) -> CliResult<()> {
+    let mut out = String::new();
+    if counts.total_files() > 0 {
+        out.push_str(&Table::results(cfg, counts).markdown());
+        if let Some(depth) = cfg.by_dir {
+            out.push_str("\n#### Directories\n\n");
+            out.push_str(&Table::dirs(cfg, &counts.dirs(depth)).markdown());
+        }
+        if cfg.per_file {
+            out.push_str("\n#### Files\n\n");
+            out.push_str(&Table::files(cfg, counts).markdown());
+        }
+    } else {
+        out.push_str("No source files were found matching the specified criteria\n");
+    }
+    if cfg.skipped && !counts.skipped().is_empty() {
+        out.push_str(&format!("\n#### Skipped\n\n{} files with an unrecognized language:\n\n{}",
+                              fmt::format_number(counts.skipped().len() as u64, cfg.thousands),
+                              Table::skipped(cfg, counts).markdown()));
+        if cfg.list_skipped {
+            out.push('\n');
+            for &(ref file, _) in counts.skipped() {
+                out.push_str(&format!("- `{}`\n", file.display()));
+            }
+        }
+    }
+    cli_try!(write!(io::stdout(), "{}", out));
+    Ok(())
+}
+
+fn write_html(cfg: &Config, counts: &Counts) -> CliResult<()> {
+    let mut out = format!("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta \
+                           charset=\"utf-8\">\n<title>Lines of code</title>\n<style>{}</style>\n\
+                           </head>\n<body>\n<h1>Lines of code</h1>\n",
+                          HTML_STYLE);
+    if counts.total_files() > 0 {
+        let results = Table::results(cfg, counts);
+        out.push_str(&results.html(true));
+        out.push_str(&format!("<h2>Lines by {}</h2>\n",
+                              cfg.group_by.heading().to_lowercase()));
+        out.push_str(&results.html_chart());
+        if let Some(depth) = cfg.by_dir {
+            out.push_str("<h2>Directories</h2>\n");
+            out.push_str(&Table::dirs(cfg, &counts.dirs(depth)).html(false));
+        }
+        if cfg.per_file {
+            out.push_str("<h2>Files</h2>\n");
+            out.push_str(&Table::files(cfg, counts).html(true));
+        }
+    } else {
+        out.push_str("<p>No source files were found matching the specified criteria</p>\n");
+    }
+    if cfg.skipped && !counts.skipped().is_empty() {
+        out.push_str(&format!("<h2>Skipped</h2>\n<p>{} files with an unrecognized \
+                               language:</p>\n{}",
+                              fmt::format_number(counts.skipped().len() as u64, cfg.thousands),
+                              Table::skipped(cfg, counts).html(true)));
+        if cfg.list_skipped {
+            out.push_str("<ul>\n");
+            for &(ref file, _) in counts.skipped() {
+                out.push_str(&format!("<li><code>{}</code></li>\n",
+                                      fmt::html_escape(&file.to_string_lossy())));
+            }
+            out.push_str("</ul>\n");
+        }
+    }
+    out.push_str(&format!("<script>{}</script>\n</body>\n</html>\n", HTML_SCRIPT));
+    cli_try!(write!(io::stdout(), "{}", out));
+    Ok(())
+}
+
+fn write_json(cfg: &Config, counts: &Counts) -> CliResult<()> {
+    let mut out = io::stdout();
+    cli_try!(write!(out,
+                    "{{\"languages\":[{}],\"totals\":{}",
+                    counts.counts()
+                        .iter()
+                        .map(|c| json_count(cfg, c))
+                        .collect::<Vec<_>>()
+                        .join(","),
+                    json_totals(cfg, &counts.totals())));
+    if cfg.group_by != GroupBy::Language {
+        cli_try!(write!(out,
+                        ",\"groups\":[{}]",
+                        counts.groups()
+                            .iter()
+                            .map(|g| json_group(cfg, g))
+                            .collect::<Vec<_>>()
+                            .join(",")));
+    }
+    if let Some(depth) = cfg.by_dir {
+        cli_try!(write!(out, ",\"directories\":{}", json_dir(cfg, &counts.dirs(depth))));
+    }
+    if cfg.per_file {
+        cli_try!(write!(out,
+                        ",\"files\":[{}]",
+                        counts.files()
+                            .iter()
+                            .map(|&(ref lang, fc)| json_file(cfg, lang, fc))
+                            .collect::<Vec<_>>()
+                            .join(",")));
+    }
+    if cfg.skipped {
+        cli_try!(write!(out,
+                        ",\"skipped\":[{}]",
+                        counts.skipped_by_ext()
+                            .into_iter()
+                            .map(|(ext, n, size)| {
+                                format!("{{\"extension\":{},\"files\":{},\"bytes\":{}}}",
+                                        fmt::json_str(&ext),
+                                        n,
+                                        size)
+                            })
+                            .collect::<Vec<_>>()
+                            .join(",")));
+        if cfg.list_skipped {
+            cli_try!(write!(out,
+                            ",\"skipped_files\":[{}]",
+                            counts.skipped()
+                                .iter()
+                                .map(|&(ref f, _)| fmt::json_str(&f.to_string_lossy()))
+                                .collect::<Vec<_>>()
+                                .join(",")));
+        }
+    }
+    if cfg.check_safety {
+        cli_try!(write!(out,
+                        ",\"unjustified_unsafe\":[{}]",
+                        counts.unjustified()
+                            .iter()
+                            .map(|&(ref f, line)| {
+                                format!("{{\"path\":{},\"line\":{}}}",
+                                        fmt::json_str(&f.to_string_lossy()),
+                                        line)
+                            })
+                            .collect::<Vec<_>>()
+                            .join(",")));
+    }
+    cli_try!(writeln!(out, "}}"));
+    Ok(())
+}
+
+// The lines of a row as the fields of a JSON object, including its unsafe lines if they were
+// counted
+fn json_lines(cfg: &Config, g: &Group) -> String {
+    format!("\"lines\":{},\"blanks\":{},\"comments\":{},\"code\":{}{}",
+            g.lines,
+            g.blanks,
+            g.comments,
+            g.code,
+            if cfg.usafe {
+                format!(",\"unsafe\":{}", g.usafe)
+            } else {
+                "".to_owned()
+            })
+}
+
+// The sizes and metrics of a row as the fields of a JSON object, each following a comma
+fn json_extra(cfg: &Config, g: &Group) -> String {
+    json_sizes(cfg, g) + &json_metrics(cfg, &g.metrics())
+}
+
+// The sizes of the lines of a row as the fields of a JSON object, each following a comma, if
+// they were requested
+fn json_sizes(cfg: &Config, g: &Group) -> String {
+    if !cfg.sizes {
+        return "".to_owned();
+    }
+    format!(",\"bytes\":{},\"chars\":{},\"avg_line_length\":{},\"longest_line\":{}",
+            g.bytes,
+            g.chars,
+            if g.lines == 0 {
+                "null".to_owned()
+            } else {
+                (g.chars as f64 / g.lines as f64).to_string()
+            },
+            g.longest_line)
+}
+
+// The configured metrics as the fields of a JSON object, each following a comma
+fn json_metrics(cfg: &Config, m: &Metrics) -> String {
+    let field = |name: &str, v: Option<f64>| {
+        format!(",{}:{}", fmt::json_str(name), v.map_or("null".to_owned(), |v| v.to_string()))
+    };
+    let mut ret = String::new();
+    for metric in &cfg.metrics {
+        match *metric {
+            Metric::Ratio => {
+                ret.push_str(&field("comment_ratio", m.comment_ratio));
+                ret.push_str(&field("blank_ratio", m.blank_ratio));
+            }
+            Metric::Avg => ret.push_str(&field("lines_per_file", m.lines_per_file)),
+            Metric::Density => ret.push_str(&field("unsafe_density", m.unsafe_density)),
+        }
+    }
+    ret
+}
+
+fn json_count(cfg: &Config, count: &Count) -> String {
+    let g = Group::from(count);
+    format!("{{\"language\":{},\"files\":{},{}{}{}{}}}",
+            fmt::json_str(count.lang.name()),
+            g.files,
+            json_lines(cfg, &g),
+            if cfg.usafe {
+                format!(",\"crate_roots\":[{}]",
+                        count.crate_roots
+                            .iter()
+                            .map(|&(ref p, policy)| {
+                                format!("{{\"path\":{},\"unsafe_code\":\"{}\"}}",
+                                        fmt::json_str(&p.to_string_lossy()),
+                                        policy.name())
+                            })
+                            .collect::<Vec<_>>()
+                            .join(","))
+            } else {
+                "".to_owned()
+            },
+            json_extra(cfg, &g),
+            if cfg.embedded && !count.embedded.is_empty() {
+                format!(",\"embedded\":[{}]",
+                        count.embedded
+                            .iter()
+                            .map(|e| json_count(cfg, e))
+                            .collect::<Vec<_>>()
+                            .join(","))
+            } else {
+                "".to_owned()
+            })
+}
+
+fn json_totals(cfg: &Config, t: &Group) -> String {
+    format!("{{\"files\":{},{}{}}}", t.files, json_lines(cfg, t), json_extra(cfg, t))
+}
+
+fn json_group(cfg: &Config, g: &Group) -> String {
+    format!("{{\"name\":{},\"files\":{},{}{}}}",
+            fmt::json_str(&g.name),
+            g.files,
+            json_lines(cfg, g),
+            json_extra(cfg, g))
+}
+
+fn json_dir(cfg: &Config, dir: &Dir) -> String {
+    let g = file_group("".to_owned(), dir.files, &dir.totals);
+    format!("{{\"path\":{},\"files\":{},{}{},\"dirs\":[{}]}}",
+            fmt::json_str(&dir.totals.path.to_string_lossy()),
+            dir.files,
+            json_lines(cfg, &g),
+            json_extra(cfg, &g),
+            dir.dirs.iter().map(|d| json_dir(cfg, d)).collect::<Vec<_>>().join(","))
+}
+
+fn json_file(cfg: &Config, lang: &Language, fc: &FileCount) -> String {
+    let g = file_group("".to_owned(), 1, fc);
+    format!("{{\"path\":{},\"language\":{},{}{}}}",
+            fmt::json_str(&fc.path.to_string_lossy()),
+            fmt::json_str(lang.name()),
+            json_lines(cfg, &g),
+            json_extra(cfg, &g))
+}
+
+// The heading of the column of unsafe lines, which the totals fill differently from other rows
+static UNSAFE_HEADING: &'static str = "Unsafe (%)";
+
+// The headings of the columns of the lines of a row
+fn line_headings(cfg: &Config) -> Vec<&'static str> {
+    let mut ret = vec!["Lines", "Blanks", "Comments", "Code"];
+    if cfg.usafe {
+        ret.push(UNSAFE_HEADING);
+    }
+    ret
+}
+
+fn line_cells(cfg: &Config, g: &Group) -> Vec<Cell> {
+    let sep = cfg.thousands;
+    let mut ret = vec![Cell::number(g.lines, sep),
+                       Cell::number(g.blanks, sep),
+                       Cell::number(g.comments, sep),
+                       Cell::number(g.code, sep)];
+    if cfg.usafe {
+        ret.push(Cell {
+            text: fmt::format_unsafe(g.usafe, g.code, sep),
+            value: Some(g.usafe as f64),
+        });
+    }
+    ret
+}
+
+// The headings of the columns of the sizes and metrics, if they were requested
+fn extra_headings(cfg: &Config) -> Vec<&'static str> {
+    let mut ret = vec![];
+    if cfg.sizes {
+        ret.extend(&["Bytes", "Chars", "Avg Line", "Longest Line"]);
+    }
+    for metric in &cfg.metrics {
+        match *metric {
+            Metric::Ratio => ret.extend(&["Comments/Code", "Blanks/Lines"]),
+            Metric::Avg => ret.push("Lines/File"),
+            Metric::Density => ret.push("Unsafe/kLoC"),
+        }
+    }
+    ret
+}
+
+fn extra_cells(cfg: &Config, g: &Group) -> Vec<Cell> {
+    let sep = cfg.thousands;
+    let mut ret = vec![];
+    if cfg.sizes {
+        ret.push(Cell::number(g.bytes, sep));
+        ret.push(Cell::number(g.chars, sep));
+        ret.push(Cell::float(if g.lines == 0 {
+                                 None
+                             } else {
+                                 Some(g.chars as f64 / g.lines as f64)
+                             },
+                             1));
+        ret.push(Cell::number(g.longest_line, sep));
+    }
+    let m = g.metrics();
+    for metric in &cfg.metrics {
+        match *metric {
+            Metric::Ratio => {
+                ret.push(Cell::float(m.comment_ratio, 2));
+                ret.push(Cell::float(m.blank_ratio, 2));
+            }
+            Metric::Avg => ret.push(Cell::float(m.lines_per_file, 1)),
+            Metric::Density => ret.push(Cell::float(m.unsafe_density, 2)),
+        }
+    }
+    ret
+}
+
+// The totals of a file, or of the `files` files of a directory, as a group named `name`
+fn file_group(name: String, files: u64, fc: &FileCount) -> Group {
+    Group {
+        name: name,
+        files: files,
+        code: fc.code,
+        comments: fc.comments,
+        blanks: fc.blanks,
+        lines: fc.lines,
+        usafe: fc.usafe,
+        bytes: fc.bytes,
+        chars: fc.chars,
+        longest_line: fc.longest_line,
+    }
+}
+
+// Escapes the text of a cell of a Markdown table
+fn md_escape(s: &str) -> String {
+    s.replace('|', "\\|").replace('<', "&lt;")
+}
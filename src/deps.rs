@@ -0,0 +1,605 @@
+use config::{Config, OutputFormat};
+use count::{Counts, UnsafePolicy};
+use error::{CliError, CliResult};
+use fmt::{self, Format};
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use tabwriter::TabWriter;
+use toml::{Parser, Value};
+
+/// A single `[[package]]` entry of a `Cargo.lock`
+#[derive(Debug)]
+pub struct Package {
+    pub name: String,
+    pub version: String,
+    pub source: Option<String>,
+    deps: Vec<String>,
+}
+
+impl Package {
+    fn from_toml(v: &Value) -> Option<Package> {
+        let name = match v.lookup("name").and_then(Value::as_str) {
+            Some(n) => n.to_owned(),
+            None => return None,
+        };
+        let version = match v.lookup("version").and_then(Value::as_str) {
+            Some(n) => n.to_owned(),
+            None => return None,
+        };
+        Some(Package {
+            name: name,
+            version: version,
+            source: v.lookup("source").and_then(Value::as_str).map(|s| s.to_owned()),
+            deps: v.lookup("dependencies")
+                .and_then(Value::as_slice)
+                .map(|deps| deps.iter().filter_map(Value::as_str).map(|s| s.to_owned()).collect())
+                .unwrap_or_default(),
+        })
+    }
+
+    /// Whether this is a member of the workspace itself, rather than a dependency
+    pub fn is_local(&self) -> bool {
+        self.source.is_none()
+    }
+
+    // Dependency specs are either `name`, `name version`, or `name version (source)` depending on
+    // the lockfile version and whether the name alone is ambiguous
+    fn matches(&self, spec: &str) -> bool {
+        let mut parts = spec.split(' ');
+        parts.next() == Some(&*self.name) && parts.next().map_or(true, |v| v == self.version)
+    }
+}
+
+/// The resolved dependency graph of a `Cargo.lock`
+#[derive(Debug)]
+pub struct Lockfile {
+    /// The directory containing the `Cargo.lock`
+    pub dir: PathBuf,
+    pub packages: Vec<Package>,
+}
+
+impl Lockfile {
+    /// Searches `start` and its parents for a `Cargo.lock`
+    pub fn find(start: &Path) -> CliResult<Lockfile> {
+        debugln!("executing; Lockfile::find; start={:?}", start);
+        let mut dir = Some(start);
+        while let Some(d) = dir {
+            let lock = d.join("Cargo.lock");
+            if lock.is_file() {
+                let mut buf = String::new();
+                cli_try!(cli_try!(File::open(&lock)).read_to_string(&mut buf));
+                return Lockfile::parse(d, &buf);
+            }
+            dir = d.parent();
+        }
+        Err(CliError::Generic(format!("could not find a Cargo.lock in '{}' or any parent \
+                                       directory, try running 'cargo generate-lockfile'",
+                                      start.display())))
+    }
+
+    pub fn parse(dir: &Path, s: &str) -> CliResult<Lockfile> {
+        let mut parser = Parser::new(s);
+        let table = match parser.parse() {
+            Some(t) => t,
+            None => {
+                let e = &parser.errors[0];
+                let (line, col) = parser.to_linecol(e.lo);
+                return Err(CliError::Generic(format!("failed to parse Cargo.lock:{}:{}: {}",
+                                                     line + 1,
+                                                     col + 1,
+                                                     e.desc)));
+            }
+        };
+        let mut packages = vec![];
+        // Version 1 lockfiles store the root crate separately
+        if let Some(root) = table.get("root").and_then(Package::from_toml) {
+            packages.push(root);
+        }
+        if let Some(pkgs) = table.get("package").and_then(Value::as_slice) {
+            packages.extend(pkgs.iter().filter_map(Package::from_toml));
+        }
+        Ok(Lockfile {
+            dir: dir.to_path_buf(),
+            packages: packages,
+        })
+    }
+
+    /// The indices of the packages `idx` directly depends on
+    pub fn deps_of(&self, idx: usize) -> Vec<usize> {
+        let mut ret: Vec<usize> = self.packages[idx]
+            .deps
+            .iter()
+            .filter_map(|spec| self.packages.iter().position(|p| p.matches(spec)))
+            .collect();
+        ret.sort();
+        ret.dedup();
+        ret
+    }
+
+    /// The indices of every non-workspace package reachable from the workspace members, sorted by
+    /// name and version
+    pub fn dependencies(&self) -> Vec<usize> {
+        let mut seen = vec![false; self.packages.len()];
+        let mut stack: Vec<usize> = (0..self.packages.len())
+            .filter(|&i| self.packages[i].is_local())
+            .collect();
+        while let Some(i) = stack.pop() {
+            for d in self.deps_of(i) {
+                if !seen[d] {
+                    seen[d] = true;
+                    stack.push(d);
+                }
+            }
+        }
+        let mut ret: Vec<usize> = (0..self.packages.len())
+            .filter(|&i| seen[i] && !self.packages[i].is_local())
+            .collect();
+        ret.sort_by(|&a, &b| {
+            let (a, b) = (&self.packages[a], &self.packages[b]);
+            (&a.name, &a.version).cmp(&(&b.name, &b.version))
+        });
+        ret
+    }
+
+    /// Finds the unpacked source of `pkg`, first in a `vendor/` directory next to the lockfile,
+    /// then in the Cargo registry cache
+    pub fn locate(&self, pkg: &Package) -> Option<PathBuf> {
+        let dir_name = format!("{}-{}", pkg.name, pkg.version);
+        let vendor = self.dir.join("vendor");
+        for cand in &[vendor.join(&dir_name), vendor.join(&pkg.name)] {
+            if cand.join("Cargo.toml").is_file() {
+                return Some(cand.clone());
+            }
+        }
+        if let Some(src) = cargo_home().map(|h| h.join("registry").join("src")) {
+            if let Ok(indices) = fs::read_dir(src) {
+                for index in indices.filter_map(|e| e.ok()) {
+                    let cand = index.path().join(&dir_name);
+                    if cand.join("Cargo.toml").is_file() {
+                        return Some(cand);
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+fn cargo_home() -> Option<PathBuf> {
+    env::var_os("CARGO_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::home_dir().map(|h| h.join(".cargo")))
+}
+
+/// The counts for a single dependency
+#[derive(Debug)]
+pub struct DepCount {
+    pub idx: usize,
+    pub path: Option<PathBuf>,
+    pub files: u64,
+    pub code: u64,
+    pub usafe: u64,
+    pub forbids_unsafe: bool,
+}
+
+impl DepCount {
+    fn new(idx: usize, path: Option<PathBuf>, cfg: &Config) -> CliResult<DepCount> {
+        let mut dc = DepCount {
+            idx: idx,
+            path: path,
+            files: 0,
+            code: 0,
+            usafe: 0,
+            forbids_unsafe: false,
+        };
+        if let Some(ref p) = dc.path {
+            let mut counts = Counts::new(cfg);
+            counts.fill_from_path(p, &None);
+            try!(counts.count());
+            dc.files = counts.total_files();
+            dc.code = counts.total_code();
+            dc.usafe = counts.total_unsafe();
+            dc.forbids_unsafe = forbids_unsafe(p);
+        }
+        Ok(dc)
+    }
+}
+
+// Only the library root is checked, since that's what dependents actually link against
+fn forbids_unsafe(dir: &Path) -> bool {
+    let mut buf = String::new();
+    File::open(dir.join("src").join("lib.rs"))
+        .and_then(|mut f| f.read_to_string(&mut buf))
+        .is_ok() && UnsafePolicy::from_source(&buf) == UnsafePolicy::Forbid
+}
+
+/// Counts the source of every dependency in the `Cargo.lock` for the current project
+pub fn count_deps(cfg: &Config) -> CliResult<()> {
+    debugln!("executing; count_deps;");
+    let lock = try!(Lockfile::find(&cli_try!(env::current_dir())));
+    verboseln!(cfg,
+               "{} {}",
+               Format::Good("Using"),
+               lock.dir.join("Cargo.lock").display());
+    let mut counts = vec![];
+    for idx in lock.dependencies() {
+        let path = lock.locate(&lock.packages[idx]);
+        verboseln!(cfg,
+                   "{} {} v{}",
+                   Format::Good("Counting"),
+                   lock.packages[idx].name,
+                   lock.packages[idx].version);
+        counts.push(try!(DepCount::new(idx, path, cfg)));
+    }
+    match (cfg.output, cfg.deps_tree) {
+        (OutputFormat::Json, false) => write_deps_json(&lock, &counts),
+        (OutputFormat::Json, true) => write_tree_json(&DepTree::new(&lock, &counts)),
+        (_, false) => write_deps(cfg, &lock, &counts),
+        (_, true) => write_tree(cfg, &DepTree::new(&lock, &counts)),
+    }
+}
+
+fn write_deps(cfg: &Config, lock: &Lockfile, counts: &[DepCount]) -> CliResult<()> {
+    let sep = cfg.thousands;
+    let mut w = TabWriter::new(vec![]);
+    cli_try!(write!(w, "\tCrate\tVersion\tFiles\tCode\tUnsafe (%)\n"));
+    cli_try!(write!(w, "\t-----\t-------\t-----\t----\t----------\n"));
+    let (mut tot_files, mut tot_code, mut tot_usafe) = (0, 0, 0);
+    let mut missing = vec![];
+    for dc in counts {
+        let pkg = &lock.packages[dc.idx];
+        if dc.path.is_none() {
+            missing.push(pkg);
+            continue;
+        }
+        tot_files += dc.files;
+        tot_code += dc.code;
+        tot_usafe += dc.usafe;
+        cli_try!(write!(w,
+                        "\t{}\t{}\t{}\t{}\t{}\n",
+                        pkg.name,
+                        pkg.version,
+                        fmt::format_number(dc.files, sep),
+                        fmt::format_number(dc.code, sep),
+                        fmt::format_unsafe(dc.usafe, dc.code, sep)));
+    }
+    cli_try!(write!(w, "\t-----\t-------\t-----\t----\t----------\n"));
+    cli_try!(write!(w,
+                    "{}\t\t\t{}\t{}\t{}\n",
+                    "Totals:",
+                    fmt::format_number(tot_files, sep),
+                    fmt::format_number(tot_code, sep),
+                    fmt::format_unsafe(tot_usafe, tot_code, sep)));
+    cli_try!(w.flush());
+
+    if counts.len() > missing.len() {
+        write!(io::stdout(),
+               "{}",
+               String::from_utf8(w.unwrap()).ok().expect("failed to get valid UTF-8 String"))
+            .expect("failed to write output");
+    } else {
+        println!("\n\tNo dependency sources were found");
+    }
+    write_missing(&missing);
+    Ok(())
+}
+
+fn write_missing(missing: &[&Package]) {
+    if !missing.is_empty() {
+        println!("\n{} the source of these dependencies could not be found (try running \
+                  'cargo fetch' or 'cargo vendor'):",
+                 Format::Warning("Skipped:"));
+        for pkg in missing {
+            println!("\t{} v{} ({})",
+                     pkg.name,
+                     pkg.version,
+                     pkg.source.as_ref().map_or("unknown source", |s| &**s));
+        }
+    }
+}
+
+fn write_deps_json(lock: &Lockfile, counts: &[DepCount]) -> CliResult<()> {
+    let mut out = io::stdout();
+    cli_try!(write!(out, "{{\"crates\":["));
+    for (i, dc) in counts.iter().enumerate() {
+        if i > 0 {
+            cli_try!(write!(out, ","));
+        }
+        cli_try!(write!(out, "{{{}}}", json_fields(&lock.packages[dc.idx], Some(dc))));
+    }
+    cli_try!(writeln!(out, "]}}"));
+    Ok(())
+}
+
+/// The dependency graph of a `Cargo.lock` along with the counts of each package, and the
+/// transitive totals of each package and everything it depends on
+#[derive(Debug)]
+pub struct DepTree<'a> {
+    lock: &'a Lockfile,
+    counts: Vec<Option<&'a DepCount>>,
+    deps: Vec<Vec<usize>>,
+    total_code: Vec<u64>,
+    total_usafe: Vec<u64>,
+}
+
+impl<'a> DepTree<'a> {
+    pub fn new(lock: &'a Lockfile, counts: &'a [DepCount]) -> Self {
+        let len = lock.packages.len();
+        let mut by_idx = vec![None; len];
+        for dc in counts {
+            by_idx[dc.idx] = Some(dc);
+        }
+        let mut tree = DepTree {
+            lock: lock,
+            counts: by_idx,
+            deps: (0..len).map(|i| lock.deps_of(i)).collect(),
+            total_code: vec![0; len],
+            total_usafe: vec![0; len],
+        };
+        // Shared dependencies are only counted once towards each total
+        for i in 0..len {
+            let mut seen = vec![false; len];
+            let mut stack = vec![i];
+            seen[i] = true;
+            while let Some(n) = stack.pop() {
+                if let Some(dc) = tree.counts[n] {
+                    tree.total_code[i] += dc.code;
+                    tree.total_usafe[i] += dc.usafe;
+                }
+                for &d in &tree.deps[n] {
+                    if !seen[d] {
+                        seen[d] = true;
+                        stack.push(d);
+                    }
+                }
+            }
+        }
+        tree
+    }
+
+    fn roots(&self) -> Vec<usize> {
+        (0..self.lock.packages.len()).filter(|&i| self.lock.packages[i].is_local()).collect()
+    }
+}
+
+fn write_tree(cfg: &Config, tree: &DepTree) -> CliResult<()> {
+    let mut out = io::stdout();
+    let mut seen = vec![false; tree.lock.packages.len()];
+    for root in tree.roots() {
+        cli_try!(write_node(&mut out, cfg, tree, root, "", "", &mut seen));
+    }
+    cli_try!(writeln!(out,
+                      "\n(*) dependencies already shown above are not repeated, {} marks crates \
+                       with #![forbid(unsafe_code)]",
+                      Format::Good("[forbid]")));
+    let missing: Vec<&Package> = tree.counts
+        .iter()
+        .enumerate()
+        .filter(|&(i, dc)| {
+            !tree.lock.packages[i].is_local() && dc.map_or(true, |d| d.path.is_none())
+        })
+        .map(|(i, _)| &tree.lock.packages[i])
+        .collect();
+    write_missing(&missing);
+    Ok(())
+}
+
+fn write_node<W: Write>(w: &mut W,
+                        cfg: &Config,
+                        tree: &DepTree,
+                        idx: usize,
+                        prefix: &str,
+                        child_prefix: &str,
+                        seen: &mut Vec<bool>)
+                        -> io::Result<()> {
+    let sep = cfg.thousands;
+    let pkg = &tree.lock.packages[idx];
+    try!(write!(w, "{}{} v{}", prefix, pkg.name, pkg.version));
+    match tree.counts[idx] {
+        Some(dc) if dc.path.is_some() => {
+            try!(write!(w,
+                        " (code {}, unsafe {}",
+                        fmt::format_number(dc.code, sep),
+                        fmt::format_number(dc.usafe, sep)));
+        }
+        Some(_) => try!(write!(w, " (source not found")),
+        None => try!(write!(w, " (workspace")),
+    }
+    if tree.deps[idx].is_empty() {
+        try!(write!(w, ")"));
+    } else {
+        try!(write!(w,
+                    "; with deps: code {}, unsafe {})",
+                    fmt::format_number(tree.total_code[idx], sep),
+                    fmt::format_number(tree.total_usafe[idx], sep)));
+    }
+    if tree.counts[idx].map_or(false, |dc| dc.forbids_unsafe) {
+        try!(write!(w, " {}", Format::Good("[forbid]")));
+    }
+    if seen[idx] && !tree.deps[idx].is_empty() {
+        return writeln!(w, " (*)");
+    }
+    try!(writeln!(w, ""));
+    seen[idx] = true;
+    let deps = &tree.deps[idx];
+    for (i, &d) in deps.iter().enumerate() {
+        let last = i == deps.len() - 1;
+        try!(write_node(w,
+                        cfg,
+                        tree,
+                        d,
+                        &format!("{}{}", child_prefix, if last { "└── " } else { "├── " }),
+                        &format!("{}{}", child_prefix, if last { "    " } else { "│   " }),
+                        seen));
+    }
+    Ok(())
+}
+
+fn write_tree_json(tree: &DepTree) -> CliResult<()> {
+    let mut out = io::stdout();
+    let mut seen = vec![false; tree.lock.packages.len()];
+    cli_try!(write!(out, "{{\"crates\":["));
+    for (i, root) in tree.roots().into_iter().enumerate() {
+        if i > 0 {
+            cli_try!(write!(out, ","));
+        }
+        cli_try!(write_node_json(&mut out, tree, root, &mut seen));
+    }
+    cli_try!(writeln!(out, "]}}"));
+    Ok(())
+}
+
+fn write_node_json<W: Write>(w: &mut W,
+                             tree: &DepTree,
+                             idx: usize,
+                             seen: &mut Vec<bool>)
+                             -> io::Result<()> {
+    try!(write!(w,
+                "{{{},\"total_code\":{},\"total_unsafe\":{}",
+                json_fields(&tree.lock.packages[idx], tree.counts[idx]),
+                tree.total_code[idx],
+                tree.total_usafe[idx]));
+    // Like the text tree, a crate's dependencies are only listed the first time it appears
+    if seen[idx] && !tree.deps[idx].is_empty() {
+        return write!(w, ",\"duplicate\":true}}");
+    }
+    seen[idx] = true;
+    try!(write!(w, ",\"dependencies\":["));
+    for (i, &d) in tree.deps[idx].iter().enumerate() {
+        if i > 0 {
+            try!(write!(w, ","));
+        }
+        try!(write_node_json(w, tree, d, seen));
+    }
+    write!(w, "]}}")
+}
+
+fn json_fields(pkg: &Package, dc: Option<&DepCount>) -> String {
+    let mut s = format!("\"name\":{},\"version\":{},\"source\":{}",
+                        fmt::json_str(&pkg.name),
+                        fmt::json_str(&pkg.version),
+                        pkg.source.as_ref().map_or("null".to_owned(), |s| fmt::json_str(s)));
+    if let Some(dc) = dc {
+        s.push_str(&format!(",\"found\":{},\"forbids_unsafe\":{},\"files\":{},\"code\":{},\
+                             \"unsafe\":{}",
+                            dc.path.is_some(),
+                            dc.forbids_unsafe,
+                            dc.files,
+                            dc.code,
+                            dc.usafe));
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Lockfile;
+    use std::path::Path;
+
+    static V1: &'static str = r#"
+[root]
+name = "app"
+version = "0.1.0"
+dependencies = [
+ "log 0.3.9 (registry+https://github.com/rust-lang/crates.io-index)",
+]
+
+[[package]]
+name = "log"
+version = "0.3.9"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+dependencies = [
+ "log 0.4.20 (registry+https://github.com/rust-lang/crates.io-index)",
+]
+
+[[package]]
+name = "log"
+version = "0.4.20"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "unused"
+version = "1.0.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+"#;
+
+    static V3: &'static str = r#"
+version = 3
+
+[[package]]
+name = "app"
+version = "0.1.0"
+dependencies = [
+ "regex",
+ "util",
+]
+
+[[package]]
+name = "util"
+version = "0.1.0"
+dependencies = [
+ "memchr 2.6.0",
+]
+
+[[package]]
+name = "regex"
+version = "1.9.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+dependencies = [
+ "memchr 2.5.0",
+]
+
+[[package]]
+name = "memchr"
+version = "2.6.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "memchr"
+version = "2.5.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+"#;
+
+    fn names(lock: &Lockfile, idxs: Vec<usize>) -> Vec<String> {
+        idxs.into_iter()
+            .map(|i| format!("{} {}", lock.packages[i].name, lock.packages[i].version))
+            .collect()
+    }
+
+    #[test]
+    fn version_1() {
+        let lock = Lockfile::parse(Path::new("."), V1).unwrap();
+        assert_eq!(lock.packages.len(), 4);
+        assert!(lock.packages[0].is_local());
+        assert_eq!(names(&lock, lock.deps_of(0)), vec!["log 0.3.9"]);
+        assert_eq!(names(&lock, lock.dependencies()), vec!["log 0.3.9", "log 0.4.20"]);
+    }
+
+    #[test]
+    fn version_3() {
+        let lock = Lockfile::parse(Path::new("."), V3).unwrap();
+        assert_eq!(names(&lock, lock.deps_of(0)), vec!["util 0.1.0", "regex 1.9.0"]);
+        assert_eq!(names(&lock, lock.dependencies()),
+                   vec!["memchr 2.5.0", "memchr 2.6.0", "regex 1.9.0"]);
+    }
+
+    #[test]
+    fn matches() {
+        let lock = Lockfile::parse(Path::new("."), V3).unwrap();
+        let memchr = &lock.packages[3];
+        assert!(memchr.matches("memchr"));
+        assert!(memchr.matches("memchr 2.6.0"));
+        assert!(memchr.matches(concat!("memchr 2.6.0 ",
+                                       "(registry+https://github.com/rust-lang/crates.io-index)")));
+        assert!(!memchr.matches("memchr 2.5.0"));
+        assert!(!memchr.matches("memchr2"));
+    }
+
+    #[test]
+    fn parse_error() {
+        assert!(Lockfile::parse(Path::new("."), "[[package]\nname = 1").is_err());
+    }
+}
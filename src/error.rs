@@ -4,13 +4,20 @@ use fmt::Format;use std::error::Error;
 use std::fmt::{Display, Formatter};
 use std::fmt::Result as FmtResult;
 
+/// The result of counting, or of running `cargo count`
 pub type CliResult<T> = Result<T, CliError>;
 
+/// An error which stops counting
 #[derive(Debug)]
 #[allow(dead_code)]
 pub enum CliError {
+    /// Any other failure, such as an I/O error, described by the message
     Generic(String),
+    /// A language given by its extension isn't known
     UnknownExt(String),
+    /// Unsafe blocks were found without a safety comment
+    MissingSafety(String),
+    /// An unexpected failure
     Unknown,
 }
 
@@ -22,21 +29,6 @@ impl CliError {
         // For now all errors are fatal
         true
     }
-
-    /// Print this error and immediately exit the program.
-    ///
-    /// If the error is non-fatal then the error is printed to stdout and the
-    /// exit status will be `0`. Otherwise, when the error is fatal, the error
-    /// is printed to stderr and the exit status will be `1`.
-    pub fn exit(&self) -> ! {
-        if self.is_fatal() {
-            wlnerr!("{}", self);
-            ::std::process::exit(1)
-        } else {
-            println!("{}", self);
-            ::std::process::exit(0)
-        }
-    }
 }
 
 impl Display for CliError {
@@ -51,6 +43,7 @@ impl Error for CliError {
         match *self {
             CliError::Generic(ref d) => &*d,
             CliError::UnknownExt(ref d) => &*d,
+            CliError::MissingSafety(ref d) => &*d,
             CliError::Unknown => "An unknown fatal error has occurred, please consider filing a bug-report!",
         }
     }
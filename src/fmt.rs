@@ -5,10 +5,15 @@ use ansi_term::ANSIString;
 #[cfg(all(feature = "color", not(target_os = "windows")))]
 use ansi_term::Colour::{Green, Red, Yellow};use std::fmt;
 
+/// Text colored by its meaning when displayed, if the `color` feature is enabled
 #[allow(dead_code)]
+#[derive(Debug)]
 pub enum Format<T> {
+    /// Bold red
     Error(T),
+    /// Yellow
     Warning(T),
+    /// Green
     Good(T),
 }
 
@@ -73,3 +78,48 @@ pub fn format_number(n: u64, sep: Option<char>) -> String {
         s
     }
 }
+
+/// The unsafe lines and their percentage of the lines of code, or nothing if there are none
+pub fn format_unsafe(usafe: u64, code: u64, sep: Option<char>) -> String {
+    if usafe == 0 || code == 0 {
+        return "".to_owned();
+    }
+    format!("{} ({:.2}%)",
+            format_number(usafe, sep),
+            (usafe as f64 / code as f64) * 100.00f64)
+}
+
+/// Escapes `s` for use in the text or attributes of an HTML document
+pub fn html_escape(s: &str) -> String {
+    let mut ret = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => ret.push_str("&amp;"),
+            '<' => ret.push_str("&lt;"),
+            '>' => ret.push_str("&gt;"),
+            '"' => ret.push_str("&quot;"),
+            '\'' => ret.push_str("&#39;"),
+            c => ret.push(c),
+        }
+    }
+    ret
+}
+
+/// Quotes and escapes `s` as a JSON string
+pub fn json_str(s: &str) -> String {
+    let mut ret = String::with_capacity(s.len() + 2);
+    ret.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => ret.push_str("\\\""),
+            '\\' => ret.push_str("\\\\"),
+            '\n' => ret.push_str("\\n"),
+            '\r' => ret.push_str("\\r"),
+            '\t' => ret.push_str("\\t"),
+            c if (c as u32) < 0x20 => ret.push_str(&format!("\\u{:04x}", c as u32)),
+            c => ret.push(c),
+        }
+    }
+    ret.push('"');
+    ret
+}
@@ -77,3 +77,17 @@ fn get_metadata(path: &PathBuf, follow_links: bool) -> Result<fs::Metadata> {
         fs::symlink_metadata(path)
     }
 }
+
+/// The path whose name is `bytes`, which on Unix may be any bytes
+#[cfg(unix)]
+pub fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+    PathBuf::from(OsStr::from_bytes(bytes))
+}
+
+// Paths elsewhere are Unicode, so any invalid UTF-8 in `bytes` is replaced by U+FFFD
+#[cfg(not(unix))]
+pub fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(&*String::from_utf8_lossy(bytes))
+}
@@ -0,0 +1,411 @@
+use comment::Block;
+use error::{CliError, CliResult};
+use language::{self, heuristics, CustomDef, Language};
+use std::env;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use regex::Regex;
+use toml::{Parser, Table, Value};
+
+/// The name of the project level language definitions file, looked for in the current directory
+pub static PROJECT_FILE: &'static str = ".cargo-count.toml";
+
+/// The languages known for this run, i.e. the built-in ones plus any defined in the user and
+/// project level definition files
+///
+/// A definitions file contains one `[[language]]` table per language:
+///
+/// ```toml
+/// [[language]]
+/// name = "Elm"
+/// extensions = ["elm"]
+/// filenames = []
+/// interpreters = ["elm"]       # matched against the `#!` line of extensionless scripts
+/// single = ["--"]
+/// multi = ["{-", "-}"]          # or a list, i.e. [["{-", "-}"], ["{--", "--}"]]
+/// strings = ["\""]
+/// unsafe_keyword = "unsafe"    # opens a block, whose lines are all unsafe
+/// unsafe_patterns = ["Debug\\."] # regexes matching single unsafe lines
+/// ```
+///
+/// Block comments which only count at the start of a line are given as tables, i.e.
+/// `multi = [{ start = "=begin", end = "=end", line_start = true }]`.
+///
+/// Only `name` and one of `extensions` or `filenames` are required. Project level definitions
+/// take precedence over user level ones, and both take precedence over the built-in languages.
+#[derive(Debug, Default, Clone)]
+pub struct LanguageTable {
+    custom: Vec<Arc<CustomDef>>,
+}
+
+impl LanguageTable {
+    /// Loads the project level definitions file and the user level one at
+    /// `$XDG_CONFIG_HOME/cargo-count/languages.toml` (or `~/.config/...`), if they exist
+    pub fn load() -> CliResult<Self> {
+        let mut table = LanguageTable::default();
+        let project = cli_try!(env::current_dir()).join(PROJECT_FILE);
+        if project.is_file() {
+            try!(table.load_file(&project));
+        }
+        if let Some(user) = user_file() {
+            if user.is_file() {
+                try!(table.load_file(&user));
+            }
+        }
+        Ok(table)
+    }
+
+    /// Loads the definitions in the file at `path`, which take precedence over the built-in
+    /// languages but not over any loaded before. Where several definitions claim the same
+    /// extension, file name or interpreter, the first one loaded is used.
+    pub fn load_file(&mut self, path: &Path) -> CliResult<()> {
+        debugln!("executing; LanguageTable::load_file; path={:?}", path);
+        let mut buf = String::new();
+        cli_try!(cli_try!(File::open(path)).read_to_string(&mut buf));
+        let mut parser = Parser::new(&buf);
+        let table = match parser.parse() {
+            Some(t) => t,
+            None => {
+                let e = &parser.errors[0];
+                let (line, col) = parser.to_linecol(e.lo);
+                return Err(CliError::Generic(format!("failed to parse {}:{}:{}: {}",
+                                                     path.display(),
+                                                     line + 1,
+                                                     col + 1,
+                                                     e.desc)));
+            }
+        };
+        let langs = match table.get("language") {
+            Some(&Value::Array(ref langs)) => langs,
+            Some(_) => return Err(invalid(path, "'language' must be an array of tables")),
+            None => return Ok(()),
+        };
+        for lang in langs {
+            let lang = match lang.as_table() {
+                Some(t) => t,
+                None => return Err(invalid(path, "'language' must be an array of tables")),
+            };
+            let def = try!(parse_def(path, lang));
+            debugln!("found language definition; def={:?}", def);
+            self.custom.push(Arc::new(def));
+        }
+        Ok(())
+    }
+
+    /// The language of files with the extension `ext`
+    pub fn from_ext(&self, ext: &str) -> Option<Language> {
+        self.custom
+            .iter()
+            .find(|def| def.exts.iter().any(|e| e == ext))
+            .map(|def| Language::Custom(def.clone()))
+            .or_else(|| Language::from_ext(ext))
+    }
+
+    /// The language of files named exactly `name`
+    pub fn from_filename(&self, name: &str) -> Option<Language> {
+        self.custom
+            .iter()
+            .find(|def| def.filenames.iter().any(|n| n == name))
+            .map(|def| Language::Custom(def.clone()))
+            .or_else(|| Language::from_filename(name))
+    }
+
+    /// The language of scripts run by `interpreter`
+    pub fn from_interpreter(&self, interpreter: &str) -> Option<Language> {
+        self.custom
+            .iter()
+            .find(|def| def.interpreters.iter().any(|i| i == interpreter))
+            .map(|def| Language::Custom(def.clone()))
+            .or_else(|| Language::from_interpreter(interpreter))
+    }
+
+    /// The language named by `tag`, an extension or language name such as those labeling the code
+    /// blocks of a Markdown document, i.e. `rs` or `rust`
+    pub fn from_tag(&self, tag: &str) -> Option<Language> {
+        self.from_ext(tag)
+            .or_else(|| {
+                self.custom
+                    .iter()
+                    .find(|def| def.name.eq_ignore_ascii_case(tag))
+                    .map(|def| Language::Custom(def.clone()))
+            })
+            .or_else(|| Language::from_name(tag))
+    }
+
+    /// The language of the file at `path`, by its file name, then its extension, and finally
+    /// the interpreter named by a `#!` on its first line
+    pub fn detect(&self, path: &Path) -> Option<Language> {
+        // The extensions and names matched are UTF-8, even where the rest of the name isn't
+        path.file_name()
+            .and_then(|n| self.from_name(&n.to_string_lossy(), path))
+            .or_else(|| self.from_shebang(path))
+    }
+
+    // Template suffixes are stripped, so `config.h.in` is a C header and `Makefile.in` a Makefile.
+    // Extensions shared by several built-in languages are settled by the contents of the file.
+    fn from_name(&self, name: &str, path: &Path) -> Option<Language> {
+        if let Some(lang) = self.from_filename(name) {
+            return Some(lang);
+        }
+        match name.rfind('.') {
+            Some(0) | None => None,
+            Some(i) => {
+                match &name[i + 1..] {
+                    "in" | "tmpl" => self.from_name(&name[..i], path),
+                    ext => {
+                        match self.from_ext(ext) {
+                            Some(lang @ Language::Custom(..)) => Some(lang),
+                            Some(lang) if heuristics::is_ambiguous(ext) => {
+                                Some(heuristics::disambiguate(ext, lang, path))
+                            }
+                            lang => lang,
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn from_shebang(&self, path: &Path) -> Option<Language> {
+        let mut buf = [0; 128];
+        let len = match File::open(path).and_then(|mut f| f.read(&mut buf)) {
+            Ok(len) => len,
+            Err(..) => return None,
+        };
+        let buf = &buf[..len];
+        if !buf.starts_with(b"#!") {
+            return None;
+        }
+        let line = String::from_utf8_lossy(&buf[2..]);
+        let line = line.lines().next().unwrap_or("");
+        let mut words = line.split_whitespace();
+        let mut interpreter = words.next().and_then(|w| w.rsplit('/').next()).unwrap_or("");
+        if interpreter == "env" {
+            // Skip any options and variable assignments, i.e. `#!/usr/bin/env -S VAR=1 python`
+            interpreter = words.find(|w| !w.starts_with('-') && !w.contains('='))
+                .unwrap_or("");
+        }
+        // Versioned interpreters such as `python3.11` are matched by their base name
+        let interpreter = interpreter.trim_right_matches(|c: char| c.is_digit(10) || c == '.');
+        debugln!("found shebang; interpreter={:?}", interpreter);
+        self.from_interpreter(interpreter)
+    }
+}
+
+fn user_file() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::home_dir().map(|h| h.join(".config")))
+        .map(|d| d.join("cargo-count").join("languages.toml"))
+}
+
+fn invalid(path: &Path, msg: &str) -> CliError {
+    CliError::Generic(format!("invalid language definition in {}: {}", path.display(), msg))
+}
+
+fn str_list(path: &Path, t: &Table, key: &str) -> CliResult<Vec<String>> {
+    let v = match t.get(key) {
+        Some(v) => v,
+        None => return Ok(vec![]),
+    };
+    let mut ret = vec![];
+    match v.as_slice() {
+        Some(items) => {
+            for item in items {
+                match item.as_str() {
+                    Some(s) => ret.push(s.to_owned()),
+                    None => {
+                        return Err(invalid(path, &format!("'{}' must be an array of strings", key)))
+                    }
+                }
+            }
+        }
+        None => return Err(invalid(path, &format!("'{}' must be an array of strings", key))),
+    }
+    Ok(ret)
+}
+
+// A list of comment or string delimiters, none of which may be empty as they'd match everywhere
+fn delimiters(path: &Path, t: &Table, key: &str) -> CliResult<Vec<String>> {
+    let ret = try!(str_list(path, t, key));
+    if ret.iter().any(|d| d.trim().is_empty()) {
+        return Err(invalid(path, &format!("'{}' delimiters must not be empty", key)));
+    }
+    Ok(ret)
+}
+
+// `multi` is either a single start and end pair, a list of pairs, or a list of tables which may
+// also set `line_start`
+fn blocks(path: &Path, t: &Table, name: &str) -> CliResult<Vec<Block<String>>> {
+    let bad = || {
+        invalid(path,
+                &format!("'multi' of language '{}' must be a start and end pair, or a list of them",
+                         name))
+    };
+    let items = match t.get("multi") {
+        Some(v) => try!(v.as_slice().ok_or_else(&bad)),
+        None => return Ok(vec![]),
+    };
+    let pair = |v: &[Value], line_start| {
+        match v {
+            [Value::String(ref start), Value::String(ref end)] => {
+                Some(Block {
+                    start: start.clone(),
+                    end: end.clone(),
+                    line_start: line_start,
+                })
+            }
+            _ => None,
+        }
+    };
+    let mut ret = vec![];
+    if let Some(block) = pair(items, false) {
+        ret.push(block);
+    } else {
+        for item in items {
+            let block = match *item {
+                Value::Array(ref v) => pair(v, false),
+                Value::Table(ref b) => {
+                    match (b.get("start"), b.get("end")) {
+                        (Some(start), Some(end)) => {
+                            let line_start = match b.get("line_start") {
+                                Some(&Value::Boolean(ls)) => ls,
+                                Some(_) => return Err(bad()),
+                                None => false,
+                            };
+                            pair(&[start.clone(), end.clone()], line_start)
+                        }
+                        _ => None,
+                    }
+                }
+                _ => None,
+            };
+            ret.push(try!(block.ok_or_else(&bad)));
+        }
+    }
+    Ok(ret)
+}
+
+fn parse_def(path: &Path, t: &Table) -> CliResult<CustomDef> {
+    let name = match t.get("name").and_then(Value::as_str) {
+        Some(n) => n.to_owned(),
+        None => return Err(invalid(path, "every language requires a 'name'")),
+    };
+    let exts = try!(str_list(path, t, "extensions"));
+    let filenames = try!(str_list(path, t, "filenames"));
+    if exts.is_empty() && filenames.is_empty() {
+        return Err(invalid(path,
+                           &format!("language '{}' requires 'extensions' or 'filenames'", name)));
+    }
+    let multi = try!(blocks(path, t, &name));
+    if multi.iter().any(|b| b.start.trim().is_empty() || b.end.trim().is_empty()) {
+        return Err(invalid(path, "'multi' delimiters must not be empty"));
+    }
+    let unsafe_keyword = match t.get("unsafe_keyword") {
+        Some(v) => {
+            match v.as_str() {
+                Some(kw) if kw.trim().is_empty() => {
+                    return Err(invalid(path, "'unsafe_keyword' must not be empty"))
+                }
+                Some(kw) => {
+                    if let Err(e) = Regex::new(&language::unsafe_keyword_regex(kw)) {
+                        return Err(invalid(path, &format!("bad unsafe keyword '{}': {}", kw, e)));
+                    }
+                    Some(kw.to_owned())
+                }
+                None => return Err(invalid(path, "'unsafe_keyword' must be a string")),
+            }
+        }
+        None => None,
+    };
+    let unsafe_patterns = try!(str_list(path, t, "unsafe_patterns"));
+    for pat in &unsafe_patterns {
+        if let Err(e) = Regex::new(pat) {
+            return Err(invalid(path, &format!("bad unsafe pattern '{}': {}", pat, e)));
+        }
+    }
+    Ok(CustomDef {
+        name: name,
+        exts: exts,
+        filenames: filenames,
+        interpreters: try!(str_list(path, t, "interpreters")),
+        single: try!(delimiters(path, t, "single")),
+        multi: multi,
+        strings: try!(delimiters(path, t, "strings")),
+        unsafe_keyword: unsafe_keyword,
+        unsafe_patterns: unsafe_patterns,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_def;
+    use comment::Block;
+    use error::CliError;
+    use language::CustomDef;
+    use std::path::Path;
+    use toml::Parser;
+
+    // Parses the single `[[language]]` of `src`, or the message of the error it's rejected with
+    fn parse(src: &str) -> Result<CustomDef, String> {
+        let table = Parser::new(src).parse().unwrap();
+        let lang = table["language"].as_slice().unwrap()[0].as_table().unwrap().clone();
+        parse_def(Path::new("l.toml"), &lang).map_err(|e| {
+            match e {
+                CliError::Generic(msg) => {
+                    msg.replace("invalid language definition in l.toml: ", "")
+                }
+                e => panic!("unexpected error {:?}", e),
+            }
+        })
+    }
+
+    #[test]
+    fn multi_forms() {
+        let def = parse("[[language]]\nname = 'a'\nextensions = ['a']\nmulti = ['{-', '-}']")
+            .unwrap();
+        let block = Block {
+            start: "{-".to_owned(),
+            end: "-}".to_owned(),
+            line_start: false,
+        };
+        assert_eq!(def.multi, vec![block]);
+        let def = parse("[[language]]\nname = 'a'\nextensions = ['a']\nmulti = [['(*', '*)'], \
+                         ['{', '}']]")
+            .unwrap();
+        assert_eq!(def.multi.len(), 2);
+        let def = parse("[[language]]\nname = 'a'\nextensions = ['a']\nmulti = [{start = \
+                         '=begin', end = '=end', line_start = true}]")
+            .unwrap();
+        assert!(def.multi[0].line_start);
+    }
+
+    #[test]
+    fn errors() {
+        let bad = |rest: &str| parse(&format!("[[language]]\n{}", rest)).unwrap_err();
+        assert_eq!(bad("extensions = ['a']"), "every language requires a 'name'");
+        assert_eq!(bad("name = 'a'"), "language 'a' requires 'extensions' or 'filenames'");
+        assert_eq!(bad("name = 'a'\nextensions = 'a'"),
+                   "'extensions' must be an array of strings");
+        assert_eq!(bad("name = 'a'\nextensions = ['a']\nmulti = ['{-']"),
+                   "'multi' of language 'a' must be a start and end pair, or a list of them");
+        assert_eq!(bad("name = 'a'\nextensions = ['a']\nmulti = [{start = '{-', end = '-}', \
+                        line_start = 1}]"),
+                   "'multi' of language 'a' must be a start and end pair, or a list of them");
+        assert_eq!(bad("name = 'a'\nextensions = ['a']\nmulti = ['{-', ' ']"),
+                   "'multi' delimiters must not be empty");
+        assert_eq!(bad("name = 'a'\nextensions = ['a']\nsingle = ['#', '']"),
+                   "'single' delimiters must not be empty");
+        assert_eq!(bad("name = 'a'\nextensions = ['a']\nstrings = [' ']"),
+                   "'strings' delimiters must not be empty");
+        assert_eq!(bad("name = 'a'\nextensions = ['a']\nunsafe_keyword = ''"),
+                   "'unsafe_keyword' must not be empty");
+        assert_eq!(bad("name = 'a'\nextensions = ['a']\nunsafe_keyword = 1"),
+                   "'unsafe_keyword' must be a string");
+        assert!(bad("name = 'a'\nextensions = ['a']\nunsafe_patterns = ['(']")
+            .starts_with("bad unsafe pattern '('"));
+    }
+}
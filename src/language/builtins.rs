@@ -0,0 +1,616 @@
+//! The built-in languages. Each language is described once here, and this table drives
+//! `Language::from_ext`, `Language::name`, the `Comment` impl, and unsafe handling.
+
+use comment::Block;
+use super::{Language, LanguageDef};
+
+// Block comment delimiters which may appear anywhere in a line, i.e. `blocks!["/*" => "*/"]`
+macro_rules! blocks {
+    ($($start:expr => $end:expr),*) => {
+        &[$(Block { start: $start, end: $end, line_start: false }),*]
+    };
+}
+
+// Fields left out of an entry have no value, i.e. no comments, strings, or unsafe handling
+const NONE: LanguageDef = LanguageDef {
+    name: "",
+    exts: &[],
+    filenames: &[],
+    interpreters: &[],
+    single: &[],
+    multi: &[],
+    strings: &[],
+    unsafe_keyword: None,
+    unsafe_patterns: &[],
+};
+
+const C_BLOCK: &'static [Block<&'static str>] = blocks!["/*" => "*/"];
+const XML_BLOCK: &'static [Block<&'static str>] = blocks!["<!--" => "-->"];
+// Plain Old Documentation, which starts with any command paragraph at the start of a line
+const POD: &'static [Block<&'static str>] = &[
+    Block { start: "=pod", end: "=cut", line_start: true },
+    Block { start: "=head", end: "=cut", line_start: true },
+    Block { start: "=over", end: "=cut", line_start: true },
+    Block { start: "=item", end: "=cut", line_start: true },
+    Block { start: "=begin", end: "=cut", line_start: true },
+    Block { start: "=for", end: "=cut", line_start: true },
+    Block { start: "=encoding", end: "=cut", line_start: true },
+];
+
+// Pointer arithmetic dereferenced in place, i.e. `*(p + 1)`, but not a multiplication such as
+// `x * (a + b)`, whose `*` follows an identifier, literal or closing bracket
+const DEREF_ARITH: &'static str = r"(?:^|[^\w)\]\s]|\breturn)\s*\*\s*\(\s*\w+\s*[-+]";
+// Unbounded copies and formatting, raw allocation, pointer casts and arithmetic, inline assembly
+const C_UNSAFE: &'static [&'static str] = &[
+    r"\b(mem(cpy|move|set)|str(n?cpy|n?cat)|w?strcpy|v?sprintf|gets|v?s?scanf|alloca)\s*\(",
+    r"\b(malloc|calloc|realloc|free)\s*\(",
+    r"\(\s*(const\s+|volatile\s+|unsigned\s+|struct\s+)*\w+\s*\*+\s*\)\s*[\w(&]",
+    DEREF_ARITH,
+    r"\b(__asm__|__asm|asm)\b",
+];
+const CPP_UNSAFE: &'static [&'static str] = &[
+    r"\b(mem(cpy|move|set)|str(n?cpy|n?cat)|w?strcpy|v?sprintf|gets|v?s?scanf|alloca)\s*\(",
+    r"\b(malloc|calloc|realloc|free)\s*\(",
+    r"\b(reinterpret_cast|const_cast)\s*<",
+    r"\(\s*(const\s+|volatile\s+|unsigned\s+|struct\s+)*\w+\s*\*+\s*\)\s*[\w(&]",
+    DEREF_ARITH,
+    r"\b(__asm__|__asm|asm)\b",
+];
+// Indirect memory operands (Intel and AT&T syntax), indirect jumps, and privileged instructions
+const ASM_UNSAFE: &'static [&'static str] = &[
+    r"\[[^\]]*\]",
+    r"-?\w*\(%\w+",
+    r"(?i)\b(jmp|call)q?\s+\*",
+    r"(?i)^\s*(\w+:\s*)?(syscall|sysenter|int|cli|sti|hlt|in|out|wrmsr|rdmsr|lgdt|lidt)\b",
+];
+const NIM_UNSAFE: &'static [&'static str] = &[
+    r"\bcast\s*\[",
+    r"\b(ptr|pointer)\b",
+    r"\b(unsafeAddr|addr)\b",
+    r"\{\.\s*emit|\basm\b",
+];
+const GO_UNSAFE: &'static [&'static str] = &[r"\bunsafe\."];
+const HASKELL_UNSAFE: &'static [&'static str] = &[r"\bunsafe[A-Z]\w*", r"\bforeign\s+import"];
+const OCAML_UNSAFE: &'static [&'static str] = &[r"\bObj\.(magic|repr|obj)\b", r"\bunsafe_\w+"];
+const SWIFT_UNSAFE: &'static [&'static str] = &[r"\b(with)?[Uu]nsafe\w*"];
+const ZIG_UNSAFE: &'static [&'static str] = &[
+    r"@(ptrCast|intToPtr|ptrFromInt|alignCast|bitCast|constCast|volatileCast)\b",
+    r"\basm\b",
+];
+
+pub static BUILTINS: &'static [(Language, LanguageDef)] = &[
+    (Language::C, LanguageDef {
+        name: "C",
+        exts: &["c"],
+        single: &["//"],
+        multi: C_BLOCK,
+        strings: &["\"", "'"],
+        unsafe_patterns: C_UNSAFE,
+        ..NONE
+    }),
+    (Language::Header, LanguageDef {
+        name: "C Header",
+        exts: &["h"],
+        single: &["//"],
+        multi: C_BLOCK,
+        strings: &["\"", "'"],
+        unsafe_patterns: C_UNSAFE,
+        ..NONE
+    }),
+    (Language::Cpp, LanguageDef {
+        name: "C++",
+        exts: &["cpp", "cp", "cc", "cxx", "c++", "C"],
+        single: &["//"],
+        multi: C_BLOCK,
+        strings: &["\"", "'"],
+        unsafe_patterns: CPP_UNSAFE,
+        ..NONE
+    }),
+    (Language::Hpp, LanguageDef {
+        name: "C++ Header",
+        exts: &["hpp", "h++"],
+        single: &["//"],
+        multi: C_BLOCK,
+        strings: &["\"", "'"],
+        unsafe_patterns: CPP_UNSAFE,
+        ..NONE
+    }),
+    (Language::Css, LanguageDef {
+        name: "CSS",
+        exts: &["css"],
+        single: &["//"],
+        multi: C_BLOCK,
+        strings: &["\"", "'"],
+        ..NONE
+    }),
+    (Language::Java, LanguageDef {
+        name: "Java",
+        exts: &["java"],
+        single: &["//"],
+        multi: C_BLOCK,
+        strings: &["\"", "'"],
+        ..NONE
+    }),
+    (Language::JavaScript, LanguageDef {
+        name: "JavaScript",
+        exts: &["js", "mjs", "cjs"],
+        interpreters: &["node", "nodejs"],
+        single: &["//"],
+        multi: C_BLOCK,
+        strings: &["\"", "'", "`"],
+        ..NONE
+    }),
+    (Language::Rust, LanguageDef {
+        name: "Rust",
+        exts: &["rs"],
+        single: &["//"],
+        multi: C_BLOCK,
+        // Not `'`, which also starts lifetimes
+        strings: &["\""],
+        unsafe_keyword: Some("unsafe"),
+        ..NONE
+    }),
+    (Language::Xml, LanguageDef {
+        name: "XML",
+        exts: &["xml"],
+        single: &["<!--"],
+        multi: XML_BLOCK,
+        ..NONE
+    }),
+    (Language::Html, LanguageDef {
+        name: "HTML",
+        exts: &["html", "htm"],
+        single: &["<!--"],
+        multi: XML_BLOCK,
+        ..NONE
+    }),
+    (Language::Python, LanguageDef {
+        name: "Python",
+        exts: &["py", "pyi", "pyw"],
+        interpreters: &["python"],
+        single: &["#"],
+        strings: &["\"\"\"", "'''", "\"", "'"],
+        ..NONE
+    }),
+    (Language::Ruby, LanguageDef {
+        name: "Ruby",
+        exts: &["rb", "rake", "gemspec"],
+        filenames: &["Rakefile", "Gemfile", "Guardfile", "Podfile", "Vagrantfile", "Brewfile"],
+        interpreters: &["ruby"],
+        single: &["#"],
+        multi: &[Block { start: "=begin", end: "=end", line_start: true }],
+        strings: &["\"", "'"],
+        ..NONE
+    }),
+    (Language::Php, LanguageDef {
+        name: "PHP",
+        exts: &["php", "php3", "php4", "php5", "phtml"],
+        interpreters: &["php"],
+        single: &["//", "#"],
+        multi: C_BLOCK,
+        strings: &["\"", "'"],
+        ..NONE
+    }),
+    (Language::Toml, LanguageDef {
+        name: "TOML",
+        exts: &["toml"],
+        single: &["#"],
+        strings: &["\"", "'"],
+        ..NONE
+    }),
+    (Language::Perl, LanguageDef {
+        name: "Perl",
+        exts: &["pl", "pm"],
+        interpreters: &["perl"],
+        single: &["#"],
+        multi: POD,
+        strings: &["\"", "'"],
+        ..NONE
+    }),
+    (Language::Go, LanguageDef {
+        name: "Go",
+        exts: &["go"],
+        single: &["//"],
+        multi: C_BLOCK,
+        strings: &["\"", "`", "'"],
+        unsafe_patterns: GO_UNSAFE,
+        ..NONE
+    }),
+    (Language::Assembly, LanguageDef {
+        name: "Assembly",
+        exts: &["agc", "asm", "a51", "inc", "nasm", "s", "ms"],
+        single: &["#"],
+        strings: &["\""],
+        unsafe_patterns: ASM_UNSAFE,
+        ..NONE
+    }),
+    (Language::Shell, LanguageDef {
+        name: "Shell",
+        exts: &["sh", "bash", "bats", "cgi", "command", "fcgi", "ksh", "tmux", "tool",
+               "zsh", "tcsh", "csh", "fish"],
+        filenames: &[".bashrc", ".bash_profile", ".zshrc", ".profile", "PKGBUILD"],
+        interpreters: &["sh", "bash", "zsh", "ksh", "dash", "ash", "fish", "csh", "tcsh"],
+        single: &["#"],
+        strings: &["\"", "'"],
+        ..NONE
+    }),
+    (Language::PowerShell, LanguageDef {
+        name: "PowerShell",
+        exts: &["ps1", "psd1", "psm1"],
+        interpreters: &["pwsh"],
+        single: &["#"],
+        multi: blocks!["<#" => "#>"],
+        strings: &["\"", "'"],
+        ..NONE
+    }),
+    (Language::D, LanguageDef {
+        name: "D",
+        exts: &["d", "di"],
+        single: &["//"],
+        multi: blocks!["/*" => "*/", "/+" => "+/"],
+        strings: &["\"", "`", "'"],
+        ..NONE
+    }),
+    (Language::Nim, LanguageDef {
+        name: "Nim",
+        exts: &["nim", "nimrod"],
+        single: &["#"],
+        multi: blocks!["#[" => "]#"],
+        strings: &["\""],
+        unsafe_patterns: NIM_UNSAFE,
+        ..NONE
+    }),
+    (Language::TypeScript, LanguageDef {
+        name: "TypeScript",
+        exts: &["ts", "mts", "cts"],
+        interpreters: &["ts-node", "deno", "bun"],
+        single: &["//"],
+        multi: C_BLOCK,
+        strings: &["\"", "'", "`"],
+        ..NONE
+    }),
+    (Language::Jsx, LanguageDef {
+        name: "JSX",
+        exts: &["jsx"],
+        single: &["//"],
+        multi: C_BLOCK,
+        strings: &["\"", "'", "`"],
+        ..NONE
+    }),
+    (Language::Tsx, LanguageDef {
+        name: "TSX",
+        exts: &["tsx"],
+        single: &["//"],
+        multi: C_BLOCK,
+        strings: &["\"", "'", "`"],
+        ..NONE
+    }),
+    (Language::Vue, LanguageDef {
+        name: "Vue",
+        exts: &["vue"],
+        single: &["<!--"],
+        multi: XML_BLOCK,
+        ..NONE
+    }),
+    (Language::Svelte, LanguageDef {
+        name: "Svelte",
+        exts: &["svelte"],
+        single: &["<!--"],
+        multi: XML_BLOCK,
+        ..NONE
+    }),
+    (Language::Scss, LanguageDef {
+        name: "SCSS",
+        exts: &["scss"],
+        single: &["//"],
+        multi: C_BLOCK,
+        strings: &["\"", "'"],
+        ..NONE
+    }),
+    (Language::Sass, LanguageDef {
+        name: "Sass",
+        exts: &["sass"],
+        single: &["//"],
+        multi: C_BLOCK,
+        strings: &["\"", "'"],
+        ..NONE
+    }),
+    (Language::Less, LanguageDef {
+        name: "Less",
+        exts: &["less"],
+        single: &["//"],
+        multi: C_BLOCK,
+        strings: &["\"", "'"],
+        ..NONE
+    }),
+    (Language::ObjectiveC, LanguageDef {
+        name: "Objective-C",
+        exts: &["m"],
+        single: &["//"],
+        multi: C_BLOCK,
+        strings: &["\"", "'"],
+        unsafe_patterns: C_UNSAFE,
+        ..NONE
+    }),
+    (Language::ObjectiveCpp, LanguageDef {
+        name: "Objective-C++",
+        exts: &["mm"],
+        single: &["//"],
+        multi: C_BLOCK,
+        strings: &["\"", "'"],
+        unsafe_patterns: CPP_UNSAFE,
+        ..NONE
+    }),
+    (Language::CSharp, LanguageDef {
+        name: "C#",
+        exts: &["cs", "csx"],
+        single: &["//"],
+        multi: C_BLOCK,
+        strings: &["\"", "'"],
+        unsafe_keyword: Some("unsafe"),
+        ..NONE
+    }),
+    (Language::FSharp, LanguageDef {
+        name: "F#",
+        exts: &["fs", "fsi", "fsx"],
+        single: &["//"],
+        multi: blocks!["(*" => "*)"],
+        strings: &["\"\"\"", "\""],
+        ..NONE
+    }),
+    (Language::Kotlin, LanguageDef {
+        name: "Kotlin",
+        exts: &["kt", "kts"],
+        single: &["//"],
+        multi: C_BLOCK,
+        strings: &["\"\"\"", "\"", "'"],
+        ..NONE
+    }),
+    (Language::Scala, LanguageDef {
+        name: "Scala",
+        exts: &["scala", "sc"],
+        interpreters: &["scala"],
+        single: &["//"],
+        multi: C_BLOCK,
+        strings: &["\"\"\"", "\""],
+        ..NONE
+    }),
+    (Language::Groovy, LanguageDef {
+        name: "Groovy",
+        exts: &["groovy", "gradle"],
+        filenames: &["Jenkinsfile"],
+        interpreters: &["groovy"],
+        single: &["//"],
+        multi: C_BLOCK,
+        strings: &["\"\"\"", "'''", "\"", "'"],
+        ..NONE
+    }),
+    (Language::Swift, LanguageDef {
+        name: "Swift",
+        exts: &["swift"],
+        interpreters: &["swift"],
+        single: &["//"],
+        multi: C_BLOCK,
+        strings: &["\"\"\"", "\""],
+        unsafe_patterns: SWIFT_UNSAFE,
+        ..NONE
+    }),
+    (Language::Dart, LanguageDef {
+        name: "Dart",
+        exts: &["dart"],
+        interpreters: &["dart"],
+        single: &["//"],
+        multi: C_BLOCK,
+        strings: &["\"\"\"", "'''", "\"", "'"],
+        ..NONE
+    }),
+    (Language::Zig, LanguageDef {
+        name: "Zig",
+        exts: &["zig"],
+        single: &["//"],
+        strings: &["\"", "'"],
+        unsafe_patterns: ZIG_UNSAFE,
+        ..NONE
+    }),
+    (Language::Haskell, LanguageDef {
+        name: "Haskell",
+        exts: &["hs"],
+        interpreters: &["runhaskell", "runghc", "stack"],
+        single: &["--"],
+        multi: blocks!["{-" => "-}"],
+        strings: &["\""],
+        unsafe_patterns: HASKELL_UNSAFE,
+        ..NONE
+    }),
+    (Language::OCaml, LanguageDef {
+        name: "OCaml",
+        exts: &["ml", "mli"],
+        interpreters: &["ocaml"],
+        multi: blocks!["(*" => "*)"],
+        strings: &["\""],
+        unsafe_patterns: OCAML_UNSAFE,
+        ..NONE
+    }),
+    (Language::Elixir, LanguageDef {
+        name: "Elixir",
+        exts: &["ex", "exs"],
+        interpreters: &["elixir"],
+        single: &["#"],
+        strings: &["\"\"\"", "\"", "'"],
+        ..NONE
+    }),
+    (Language::Erlang, LanguageDef {
+        name: "Erlang",
+        exts: &["erl", "hrl"],
+        interpreters: &["escript"],
+        single: &["%"],
+        strings: &["\"", "'"],
+        ..NONE
+    }),
+    (Language::Clojure, LanguageDef {
+        name: "Clojure",
+        exts: &["clj", "cljs", "cljc", "edn"],
+        single: &[";"],
+        strings: &["\""],
+        ..NONE
+    }),
+    (Language::Lisp, LanguageDef {
+        name: "Lisp",
+        exts: &["lisp", "lsp", "el"],
+        single: &[";"],
+        multi: blocks!["#|" => "|#"],
+        strings: &["\""],
+        ..NONE
+    }),
+    (Language::Lua, LanguageDef {
+        name: "Lua",
+        exts: &["lua"],
+        interpreters: &["lua", "luajit"],
+        single: &["--"],
+        // Long brackets may have any level, i.e. `--[==[` and `]==]`
+        multi: blocks!["--[[" => "]]",
+                       "--[=[" => "]=]",
+                       "--[==[" => "]==]",
+                       "--[===[" => "]===]"],
+        strings: &["\"", "'"],
+        ..NONE
+    }),
+    (Language::R, LanguageDef {
+        name: "R",
+        exts: &["r", "R"],
+        interpreters: &["Rscript"],
+        single: &["#"],
+        strings: &["\"", "'"],
+        ..NONE
+    }),
+    (Language::Julia, LanguageDef {
+        name: "Julia",
+        exts: &["jl"],
+        interpreters: &["julia"],
+        single: &["#"],
+        multi: blocks!["#=" => "=#"],
+        strings: &["\"\"\"", "\""],
+        ..NONE
+    }),
+    (Language::Pascal, LanguageDef {
+        name: "Pascal",
+        exts: &["pas", "pp", "dpr", "lpr"],
+        single: &["//"],
+        multi: blocks!["{" => "}", "(*" => "*)"],
+        strings: &["'"],
+        ..NONE
+    }),
+    (Language::Prolog, LanguageDef {
+        name: "Prolog",
+        exts: &["pro"],
+        single: &["%"],
+        multi: C_BLOCK,
+        strings: &["\"", "'"],
+        ..NONE
+    }),
+    (Language::Sql, LanguageDef {
+        name: "SQL",
+        exts: &["sql"],
+        single: &["--"],
+        multi: C_BLOCK,
+        strings: &["'", "\""],
+        ..NONE
+    }),
+    (Language::Protobuf, LanguageDef {
+        name: "Protocol Buffers",
+        exts: &["proto"],
+        single: &["//"],
+        multi: C_BLOCK,
+        strings: &["\"", "'"],
+        ..NONE
+    }),
+    (Language::GraphQl, LanguageDef {
+        name: "GraphQL",
+        exts: &["graphql", "gql"],
+        single: &["#"],
+        strings: &["\"\"\"", "\""],
+        ..NONE
+    }),
+    (Language::Json, LanguageDef {
+        name: "JSON",
+        exts: &["json"],
+        strings: &["\""],
+        ..NONE
+    }),
+    (Language::Yaml, LanguageDef {
+        name: "YAML",
+        exts: &["yaml", "yml"],
+        single: &["#"],
+        strings: &["\"", "'"],
+        ..NONE
+    }),
+    (Language::Ini, LanguageDef {
+        name: "INI",
+        exts: &["ini", "cfg"],
+        single: &[";", "#"],
+        ..NONE
+    }),
+    (Language::Hcl, LanguageDef {
+        name: "HCL",
+        exts: &["tf", "hcl"],
+        single: &["#", "//"],
+        multi: C_BLOCK,
+        strings: &["\""],
+        ..NONE
+    }),
+    (Language::Nix, LanguageDef {
+        name: "Nix",
+        exts: &["nix"],
+        single: &["#"],
+        multi: C_BLOCK,
+        strings: &["''", "\""],
+        ..NONE
+    }),
+    (Language::Markdown, LanguageDef {
+        name: "Markdown",
+        exts: &["md", "markdown"],
+        single: &["<!--"],
+        multi: XML_BLOCK,
+        ..NONE
+    }),
+    (Language::Dockerfile, LanguageDef {
+        name: "Dockerfile",
+        exts: &["dockerfile"],
+        filenames: &["Dockerfile", "Containerfile"],
+        single: &["#"],
+        strings: &["\"", "'"],
+        ..NONE
+    }),
+    (Language::Makefile, LanguageDef {
+        name: "Makefile",
+        exts: &["mk", "mak", "make"],
+        filenames: &["Makefile", "makefile", "GNUmakefile"],
+        interpreters: &["make"],
+        single: &["#"],
+        ..NONE
+    }),
+    (Language::CMake, LanguageDef {
+        name: "CMake",
+        exts: &["cmake"],
+        filenames: &["CMakeLists.txt"],
+        single: &["#"],
+        multi: blocks!["#[[" => "]]"],
+        strings: &["\""],
+        ..NONE
+    }),
+    (Language::Batch, LanguageDef {
+        name: "Batch",
+        exts: &["bat", "cmd"],
+        single: &["REM ", "rem ", "@REM ", "@rem ", "::"],
+        strings: &["\""],
+        ..NONE
+    }),
+    (Language::Starlark, LanguageDef {
+        name: "Starlark",
+        exts: &["bzl", "star", "bazel"],
+        filenames: &["BUILD", "WORKSPACE"],
+        single: &["#"],
+        strings: &["\"\"\"", "'''", "\"", "'"],
+        ..NONE
+    }),
+];
@@ -0,0 +1,289 @@
+use langdefs::LanguageTable;
+use language::Language;
+
+/// A run of consecutive lines of a file in a single language, as `(line_no, line)` pairs
+#[derive(Debug)]
+pub struct Region<'a> {
+    pub lang: Language,
+    pub lines: Vec<(usize, &'a str)>,
+}
+
+/// Whether files in `lang` may contain regions written in other languages, i.e. the `<script>`
+/// blocks of an HTML page or the fenced code blocks of a Markdown document
+pub fn is_host(lang: &Language) -> bool {
+    match *lang {
+        Language::Html | Language::Vue | Language::Svelte | Language::Markdown => true,
+        _ => false,
+    }
+}
+
+/// Splits `buffer`, the contents of a file in the host language `lang`, into regions of either
+/// the host or an embedded language. The lines opening and closing an embedded region, such as
+/// `<script>` and `</script>`, belong to the host.
+pub fn split<'a>(lang: &Language, buffer: &'a str, langs: &LanguageTable) -> Vec<Region<'a>> {
+    let mut splitter = Splitter {
+        host: lang.clone(),
+        regions: vec![],
+    };
+    if *lang == Language::Markdown {
+        splitter.split_fences(buffer, langs);
+    } else {
+        splitter.split_tags(buffer, langs);
+    }
+    splitter.regions
+}
+
+struct Splitter<'a> {
+    host: Language,
+    regions: Vec<Region<'a>>,
+}
+
+impl<'a> Splitter<'a> {
+    fn push(&mut self, lang: Option<Language>, line_no: usize, line: &'a str) {
+        let lang = lang.unwrap_or_else(|| self.host.clone());
+        if let Some(region) = self.regions.last_mut() {
+            if region.lang == lang {
+                region.lines.push((line_no, line));
+                return;
+            }
+        }
+        self.regions.push(Region {
+            lang: lang,
+            lines: vec![(line_no, line)],
+        });
+    }
+
+    // `<script>` and `<style>` elements of HTML, Vue and Svelte files. Tags inside `<!-- -->`
+    // comments are ignored, and an opening tag may span several lines.
+    fn split_tags(&mut self, buffer: &'a str, langs: &LanguageTable) {
+        let mut in_comment = false;
+        // The element being opened, and its attributes so far, while inside the opening tag
+        let mut opening: Option<(&'static str, String)> = None;
+        // The element whose contents are being read, and their language
+        let mut inside: Option<(&'static str, Option<Language>)> = None;
+        for (line_no, line) in buffer.lines().enumerate() {
+            let lower = line.to_lowercase();
+            if let Some((elem, ref lang)) = inside {
+                if lower.contains(&*format!("</{}", elem)) {
+                    inside = None;
+                    self.push(None, line_no, line);
+                } else {
+                    self.push(lang.clone(), line_no, line);
+                }
+                continue;
+            }
+            self.push(None, line_no, line);
+            let mut rest = &*lower;
+            if let Some((elem, mut attrs)) = opening.take() {
+                match rest.find('>') {
+                    Some(end) => {
+                        attrs.push_str(&rest[..end]);
+                        rest = &rest[end + 1..];
+                        inside = Some((elem, embedded_lang(elem, &attrs, langs)));
+                    }
+                    None => {
+                        attrs.push(' ');
+                        attrs.push_str(rest);
+                        opening = Some((elem, attrs));
+                        continue;
+                    }
+                }
+            }
+            loop {
+                if in_comment {
+                    match rest.find("-->") {
+                        Some(end) => {
+                            in_comment = false;
+                            rest = &rest[end + 3..];
+                        }
+                        None => break,
+                    }
+                }
+                if let Some((elem, lang)) = inside.take() {
+                    // The element was closed on the same line it was opened on
+                    match rest.find(&*format!("</{}", elem)) {
+                        Some(end) => {
+                            rest = &rest[end..];
+                        }
+                        None => {
+                            inside = Some((elem, lang));
+                            break;
+                        }
+                    }
+                }
+                let comment = rest.find("<!--");
+                let tag = ["script", "style"]
+                    .iter()
+                    .filter_map(|elem| rest.find(&*format!("<{}", elem)).map(|pos| (pos, *elem)))
+                    .filter(|&(pos, elem)| {
+                        // Only the element itself, and not i.e. `<scripts>`
+                        rest[pos + 1 + elem.len()..]
+                            .chars()
+                            .next()
+                            .map_or(true, |c| c == '>' || c.is_whitespace())
+                    })
+                    .min();
+                match (comment, tag) {
+                    (Some(c), Some((t, _))) if c < t => {
+                        in_comment = true;
+                        rest = &rest[c + 4..];
+                    }
+                    (Some(c), None) => {
+                        in_comment = true;
+                        rest = &rest[c + 4..];
+                    }
+                    (_, Some((t, elem))) => {
+                        let attrs = &rest[t + 1 + elem.len()..];
+                        match attrs.find('>') {
+                            Some(end) => {
+                                inside = Some((elem, embedded_lang(elem, &attrs[..end], langs)));
+                                rest = &attrs[end + 1..];
+                            }
+                            None => {
+                                opening = Some((elem, attrs.to_owned()));
+                                break;
+                            }
+                        }
+                    }
+                    (None, None) => break,
+                }
+            }
+        }
+    }
+
+    // Fenced code blocks of Markdown, whose info string names their language. Blocks in an unknown
+    // language are counted as Markdown.
+    fn split_fences(&mut self, buffer: &'a str, langs: &LanguageTable) {
+        // The fence which opened the current code block, and the block's language
+        let mut fence: Option<(&'a str, Option<Language>)> = None;
+        for (line_no, line) in buffer.lines().enumerate() {
+            let trimmed = line.trim();
+            if let Some((open, ref lang)) = fence {
+                let c = open.chars().next().unwrap();
+                if trimmed.len() >= open.len() && trimmed.chars().all(|t| t == c) {
+                    fence = None;
+                    self.push(None, line_no, line);
+                } else {
+                    self.push(lang.clone(), line_no, line);
+                }
+                continue;
+            }
+            self.push(None, line_no, line);
+            for c in &['`', '~'] {
+                let len = trimmed.chars().take_while(|t| t == c).count();
+                if len >= 3 {
+                    // i.e. "```rust", "```rust,ignore" or "``` {.rust}"
+                    let info = trimmed[len..].trim_left_matches(|t: char| {
+                        t.is_whitespace() || t == '{' || t == '.'
+                    });
+                    let tag = info.split(|t: char| t.is_whitespace() || t == ',' || t == '}')
+                        .next()
+                        .unwrap_or("");
+                    let lang = if tag.is_empty() {
+                        None
+                    } else {
+                        langs.from_tag(tag)
+                    };
+                    debugln!("found code fence; tag={:?}; lang={:?}", tag, lang);
+                    fence = Some((&trimmed[..len], lang));
+                    break;
+                }
+            }
+        }
+    }
+}
+
+// The language of the contents of a `<script>` or `<style>` element with the attributes `attrs`,
+// if it's known
+fn embedded_lang(elem: &str, attrs: &str, langs: &LanguageTable) -> Option<Language> {
+    debugln!("found embedded element; elem={:?}; attrs={:?}", elem, attrs);
+    if let Some(lang) = attr(attrs, "lang") {
+        return langs.from_tag(lang);
+    }
+    match (elem, attr(attrs, "type")) {
+        ("style", None) => Some(Language::Css),
+        ("style", Some(ty)) if ty.ends_with("css") => Some(Language::Css),
+        ("script", None) => Some(Language::JavaScript),
+        ("script", Some(ty)) if ty.contains("typescript") => Some(Language::TypeScript),
+        ("script", Some(ty)) if ty.contains("json") || ty == "importmap" => Some(Language::Json),
+        ("script", Some(ty)) if ty.contains("javascript") || ty.contains("ecmascript") ||
+                                ty == "module" => Some(Language::JavaScript),
+        // Templates and other data blocks, i.e. `text/x-template`
+        _ => None,
+    }
+}
+
+// The value of the attribute `name` of a tag, without any quotes
+fn attr<'a>(attrs: &'a str, name: &str) -> Option<&'a str> {
+    let mut rest = attrs;
+    while let Some(pos) = rest.find(name) {
+        let preceded = rest[..pos].chars().next_back().map_or(true, char::is_whitespace);
+        let after = rest[pos + name.len()..].trim_left();
+        if preceded && after.starts_with('=') {
+            let value = after[1..].trim_left();
+            return match value.chars().next() {
+                Some(q) if q == '"' || q == '\'' => value[1..].split(q).next(),
+                _ => value.split(|c: char| c.is_whitespace() || c == '/').next(),
+            };
+        }
+        rest = &rest[pos + name.len()..];
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::split;
+    use langdefs::LanguageTable;
+    use language::Language;
+
+    // The language of each region of `src`, with the numbers of its lines
+    fn regions(lang: Language, src: &str) -> Vec<(Language, Vec<usize>)> {
+        split(&lang, src, &LanguageTable::default())
+            .into_iter()
+            .map(|r| (r.lang, r.lines.into_iter().map(|(n, _)| n).collect()))
+            .collect()
+    }
+
+    #[test]
+    fn fences() {
+        let src = "# A\n```rust,ignore\nfn a() {}\n````\n~~~ {.python}\nx = 1\n~~~\n```foo\nb\n\
+                   ```\n";
+        assert_eq!(regions(Language::Markdown, src),
+                   vec![(Language::Markdown, vec![0, 1]),
+                        (Language::Rust, vec![2]),
+                        (Language::Markdown, vec![3, 4]),
+                        (Language::Python, vec![5]),
+                        (Language::Markdown, vec![6, 7, 8, 9])]);
+        // A closing fence must be at least as long as the opening one
+        assert_eq!(regions(Language::Markdown, "````sh\n```\n````"),
+                   vec![(Language::Markdown, vec![0]),
+                        (Language::Shell, vec![1]),
+                        (Language::Markdown, vec![2])]);
+    }
+
+    #[test]
+    fn scripts_and_styles() {
+        let src = "<p>\n<script>\nvar a;\n</script>\n<style type=\"text/css\">\np {}\n</style>\n\
+                   <script type=\"text/x-template\">\n<div></div>\n</script>";
+        assert_eq!(regions(Language::Html, src),
+                   vec![(Language::Html, vec![0, 1]),
+                        (Language::JavaScript, vec![2]),
+                        (Language::Html, vec![3, 4]),
+                        (Language::Css, vec![5]),
+                        (Language::Html, vec![6, 7, 8, 9])]);
+    }
+
+    #[test]
+    fn tags() {
+        // Commented out, closed on the same line, and not a script element
+        let src = "<!-- <script> -->\n<script>a()</script>\n<scripts>\nb\n<!--\n<style>\n-->";
+        assert_eq!(regions(Language::Html, src), vec![(Language::Html, (0..7).collect())]);
+        // An opening tag spanning several lines, with the language in an attribute
+        let src = "<script\n  lang='ts'>\nlet a: number;\n</script>";
+        assert_eq!(regions(Language::Vue, src),
+                   vec![(Language::Vue, vec![0, 1]),
+                        (Language::TypeScript, vec![2]),
+                        (Language::Vue, vec![3])]);
+    }
+}
@@ -0,0 +1,193 @@
+use language::Language;
+use regex::Regex;
+
+use std::collections::VecDeque;
+
+/// Tracks the heredocs of a Shell, Ruby, Perl or PHP file across its lines
+///
+/// A heredoc's body is a string literal running until a line holding just its terminator, so
+/// lines in it which look like comments, i.e. `# key = value` in an embedded config, are code.
+#[derive(Debug)]
+pub struct Heredocs<'r> {
+    lang: Language,
+    re: &'r Regex,
+    // The terminators of the heredocs opened so far, as several may open on the same line
+    open: VecDeque<String>,
+}
+
+/// The regex matching the start of a heredoc in `lang`, if it has them, which is compiled once
+/// for all of its files
+pub fn regex(lang: &Language) -> Option<Regex> {
+    // The terminator is captured as `term`, and may be quoted to disable interpolation
+    let re = match *lang {
+        // `<<EOF`, `<<-EOF`, `<< 'EOF'` and `<<\EOF`, but not the `<<<` of a here-string
+        Language::Shell => {
+            r#"(?:^|[^<])<<-?[ \t]*(?P<quote>\\|["']?)(?P<term>[A-Za-z_][A-Za-z0-9_]*)["']?"#
+        }
+        // `<<EOF`, `<<-EOF` and `<<~EOF`, but not `x << y`
+        Language::Ruby => {
+            r#"<<(?P<flag>[~-]?)(?P<quote>["'`]?)(?P<term>[A-Za-z_][A-Za-z0-9_]*)["'`]?"#
+        }
+        Language::Perl => r#"<<~?(?P<quote>["']?)(?P<term>[A-Za-z_][A-Za-z0-9_]*)["']?"#,
+        // Heredocs and nowdocs, i.e. `<<<EOT` and `<<<'EOT'`
+        Language::Php => r#"<<<[ \t]*(?P<quote>["']?)(?P<term>[A-Za-z_][A-Za-z0-9_]*)["']?"#,
+        _ => return None,
+    };
+    Some(Regex::new(re).unwrap())
+}
+
+impl<'r> Heredocs<'r> {
+    /// A tracker for `lang`, whose heredocs are matched by `re` as returned by `regex`
+    pub fn new(lang: &Language, re: &'r Regex) -> Self {
+        Heredocs {
+            lang: lang.clone(),
+            re: re,
+            open: VecDeque::new(),
+        }
+    }
+
+    /// Whether the trimmed `line` is part of a heredoc opened on a previous line, including its
+    /// terminator
+    pub fn in_body(&mut self, line: &str) -> bool {
+        let closes = match self.open.front() {
+            Some(term) => self.terminates(term, line),
+            None => return false,
+        };
+        if closes {
+            debugln!("line closes a heredoc; line={:?}", line);
+            self.open.pop_front();
+        }
+        true
+    }
+
+    /// The terminator of the heredoc whose body comes next, if any is open
+    pub fn pending(&self) -> Option<&str> {
+        self.open.front().map(|term| &**term)
+    }
+
+    /// Records the heredocs opened by the trimmed `line` of code
+    pub fn scan(&mut self, line: &str) {
+        for caps in self.re.captures_iter(line) {
+            let start = caps.pos(0).map_or(0, |(start, _)| start);
+            let start = start + line[start..].find('<').unwrap_or(0);
+            if in_string(&line[..start]) {
+                continue;
+            }
+            let quoted = caps.name("quote").map_or(false, |q| !q.is_empty());
+            let term = match caps.name("term") {
+                Some(term) => term,
+                None => continue,
+            };
+            let is_heredoc = match self.lang {
+                // `$(( 1 << bits ))` is a shift, whose operand is never a quoted delimiter
+                Language::Shell => quoted || !in_arithmetic(&line[..start]),
+                // Without a quote, `~` or `-`, Ruby only takes `<<` as a heredoc after a method
+                // name, which can't be told apart from a variable here. Delimiters are upper case
+                // by convention, so `x <<y` and `x<<Y` are shifts.
+                Language::Ruby => {
+                    quoted || caps.name("flag").map_or(false, |f| !f.is_empty()) ||
+                    (!term.chars().any(|c| c.is_lowercase()) &&
+                     !line[..start]
+                        .chars()
+                        .last()
+                        .map_or(false, |c| c.is_alphanumeric() || "_)]}".contains(c)))
+                }
+                _ => true,
+            };
+            if is_heredoc {
+                debugln!("found heredoc; terminator={:?}", term);
+                self.open.push_back(term.to_owned());
+            }
+        }
+    }
+
+    fn terminates(&self, term: &str, line: &str) -> bool {
+        match self.lang {
+            // The terminator may be followed by the rest of the statement, i.e. `EOT;`
+            Language::Php => {
+                line.starts_with(term) &&
+                !line[term.len()..]
+                    .chars()
+                    .next()
+                    .map_or(false, |c| c.is_alphanumeric() || c == '_')
+            }
+            _ => line == term,
+        }
+    }
+}
+
+// Whether the end of `before` is inside the arithmetic of a Shell `$(( ))` or `(( ))`
+fn in_arithmetic(before: &str) -> bool {
+    let bytes = before.as_bytes();
+    // The depth of parentheses, and that outside each arithmetic expression still open
+    let mut depth = 0;
+    let mut open = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'(' if bytes.get(i + 1) == Some(&b'(') => {
+                open.push(depth);
+                depth += 2;
+                i += 1;
+            }
+            b'(' => depth += 1,
+            b')' if depth > 0 => {
+                depth -= 1;
+                if open.last().map_or(false, |&d| depth <= d) {
+                    open.pop();
+                }
+            }
+            _ => (),
+        }
+        i += 1;
+    }
+    !open.is_empty()
+}
+
+// Whether the end of `before` is inside a quoted string
+fn in_string(before: &str) -> bool {
+    let mut quote = None;
+    let mut escaped = false;
+    for c in before.chars() {
+        match (quote, c) {
+            _ if escaped => escaped = false,
+            (_, '\\') => escaped = true,
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (Some(q), c) if q == c => quote = None,
+            _ => (),
+        }
+    }
+    quote.is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{regex, Heredocs};
+    use language::Language;
+
+    fn opened(lang: Language, line: &str) -> Option<String> {
+        let re = regex(&lang).unwrap();
+        let mut h = Heredocs::new(&lang, &re);
+        h.scan(line);
+        h.pending().map(|term| term.to_owned())
+    }
+
+    #[test]
+    fn shell_shift_in_arithmetic() {
+        assert_eq!(opened(Language::Shell, "echo $(( 1 << bits ))"), None);
+        assert_eq!(opened(Language::Shell, "(( x = y <<2 ))"), None);
+        assert_eq!(opened(Language::Shell, "n=$(( (1 << bits) - 1 ))"), None);
+        assert_eq!(opened(Language::Shell, "echo $(( 1 << 2 )) && cat << EOF"),
+                   Some("EOF".to_owned()));
+        assert_eq!(opened(Language::Shell, "cat <<-'END'"), Some("END".to_owned()));
+    }
+
+    #[test]
+    fn ruby_shift() {
+        assert_eq!(opened(Language::Ruby, "x <<y"), None);
+        assert_eq!(opened(Language::Ruby, "list<<ITEM"), None);
+        assert_eq!(opened(Language::Ruby, "puts <<EOS"), Some("EOS".to_owned()));
+        assert_eq!(opened(Language::Ruby, "sql = <<~sql"), Some("sql".to_owned()));
+        assert_eq!(opened(Language::Ruby, "f(<<'eos')"), Some("eos".to_owned()));
+    }
+}
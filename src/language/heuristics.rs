@@ -0,0 +1,168 @@
+//! Content based heuristics for extensions which are shared by several languages.
+
+use super::Language;
+use regex::Regex;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+// Only the start of a file is sniffed, which is where the telling constructs (includes, imports,
+// declarations, dependency rules) tend to be
+const SNIFF_LEN: u64 = 16 * 1024;
+
+// The regexes of the heuristics, compiled when the first ambiguous file is found and reused for
+// the rest
+thread_local!(static RES: Res = Res::new());
+
+struct Res {
+    objc: Regex,
+    cpp: Regex,
+    pascal: Regex,
+    make_rule: Regex,
+    dlang: Regex,
+    perl: Regex,
+    prolog: Regex,
+}
+
+impl Res {
+    fn new() -> Self {
+        let re = |re: &str| Regex::new(re).unwrap();
+        Res {
+            objc: re(r"(?m)^\s*(@interface|@implementation|@protocol|@property|@end\b|#import\s)"),
+            cpp: re(concat!(r"(?m)^\s*(class\s+\w+\s*(final\s*)?[:{]|namespace(\s+\w+)?\s*\{|",
+                            r"template\s*<|using\s+namespace\s|(public|private|protected)\s*:)|",
+                            r"\bstd::|#include\s*<(iostream|string|vector|memory|map|algorithm|",
+                            r"utility)>|\b(virtual|constexpr|nullptr)\b")),
+            pascal: re(concat!(r"(?im)^\s*(procedure|function|constructor|destructor)\s+",
+                               r"\w+.*;\s*$|^\s*(begin|end[;.])\s*$|\{\$\w+")),
+            make_rule: re(r"(?m)^[\w./+\\-]+(\s+[\w./+\\-]+)*\s*:(\s|$)"),
+            dlang: re(concat!(r"(?m)^\s*(module|import|void|auto|struct|class|enum|int|static|",
+                              r"unittest)\b.*[;{]")),
+            perl: re(concat!(r"(?m)^#!.*perl|^\s*(use\s+(strict|warnings|\w+(::\w+)*)|",
+                             r"my\s+[$@%]|sub\s+\w+|package\s+\w)")),
+            prolog: re(r"(?m)^:-\s*\w|^[a-z]\w*(\(.*\))?\s*:-"),
+        }
+    }
+}
+
+/// Whether files with the extension `ext` need their contents checked to know their language
+pub fn is_ambiguous(ext: &str) -> bool {
+    match ext {
+        "h" | "C" | "inc" | "d" | "pl" => true,
+        _ => false,
+    }
+}
+
+/// Picks the language of the file at `path` with the ambiguous extension `ext` by its contents,
+/// falling back to `lang`, the language the extension maps to, if nothing is conclusive
+pub fn disambiguate(ext: &str, lang: Language, path: &Path) -> Language {
+    let mut buf = vec![];
+    if File::open(path).and_then(|f| f.take(SNIFF_LEN).read_to_end(&mut buf)).is_err() {
+        return lang;
+    }
+    let found = by_contents(ext, &String::from_utf8_lossy(&buf));
+    debugln!("disambiguated; ext={:?}; found={:?}", ext, found);
+    found.unwrap_or(lang)
+}
+
+// The language of `src`, the start of a file with the ambiguous extension `ext`, if its contents
+// are conclusive
+fn by_contents(ext: &str, src: &str) -> Option<Language> {
+    RES.with(|res| {
+        match ext {
+            "h" => header(res, src),
+            "C" => {
+                if res.cpp.is_match(src) {
+                    Some(Language::Cpp)
+                } else {
+                    Some(Language::C)
+                }
+            }
+            "inc" => include(res, src),
+            "d" => dlang(res, src),
+            "pl" => perl(res, src),
+            _ => None,
+        }
+    })
+}
+
+// `.h` is a C header unless it uses Objective-C or C++ constructs
+fn header(res: &Res, src: &str) -> Option<Language> {
+    if res.objc.is_match(src) {
+        Some(Language::ObjectiveC)
+    } else if res.cpp.is_match(src) {
+        Some(Language::Hpp)
+    } else {
+        None
+    }
+}
+
+// `.inc` is mapped to Assembly, but is just as often an included PHP or Pascal source
+fn include(res: &Res, src: &str) -> Option<Language> {
+    if src.contains("<?php") || src.contains("<?=") {
+        Some(Language::Php)
+    } else if res.pascal.is_match(src) {
+        Some(Language::Pascal)
+    } else {
+        None
+    }
+}
+
+// Compilers emit `.d` Makefile fragments listing the dependencies of each object, i.e.
+// `foo.o: foo.c foo.h \`, which contain no D statements
+fn dlang(res: &Res, src: &str) -> Option<Language> {
+    if res.make_rule.is_match(src) && !res.dlang.is_match(src) {
+        Some(Language::Makefile)
+    } else {
+        None
+    }
+}
+
+// `.pl` is Perl unless it's made of Prolog clauses and directives
+fn perl(res: &Res, src: &str) -> Option<Language> {
+    if res.prolog.is_match(src) && !res.perl.is_match(src) {
+        Some(Language::Prolog)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::by_contents;
+    use language::Language;
+
+    #[test]
+    fn headers() {
+        assert_eq!(by_contents("h", "@interface Foo : NSObject\n@end"),
+                   Some(Language::ObjectiveC));
+        assert_eq!(by_contents("h", "namespace foo {\n}"), Some(Language::Hpp));
+        assert_eq!(by_contents("h", "#include <vector>\nstd::vector<int> v;"),
+                   Some(Language::Hpp));
+        assert_eq!(by_contents("h", "#include <stdio.h>\nint f(void);"), None);
+        assert_eq!(by_contents("C", "class Foo {\n};"), Some(Language::Cpp));
+        assert_eq!(by_contents("C", "int main(void) {}"), Some(Language::C));
+    }
+
+    #[test]
+    fn includes() {
+        assert_eq!(by_contents("inc", "<?php\necho 1;"), Some(Language::Php));
+        assert_eq!(by_contents("inc", "procedure Foo;\nbegin\nend;"), Some(Language::Pascal));
+        assert_eq!(by_contents("inc", "mov eax, 1"), None);
+    }
+
+    #[test]
+    fn makefile_fragments() {
+        assert_eq!(by_contents("d", "foo.o: foo.c foo.h \\\n  bar.h"),
+                   Some(Language::Makefile));
+        assert_eq!(by_contents("d", "import std.stdio;\nvoid main() {}"), None);
+    }
+
+    #[test]
+    fn prolog() {
+        assert_eq!(by_contents("pl", ":- module(a, []).\nparent(a, b).\nanc(X) :- parent(X, _)."),
+                   Some(Language::Prolog));
+        assert_eq!(by_contents("pl", "use strict;\nmy $x = 1;"), None);
+        assert_eq!(by_contents("pl", "#!/usr/bin/perl\nprint 1;"), None);
+    }
+}
@@ -0,0 +1,354 @@
+mod builtins;
+pub mod embedded;
+pub mod heredoc;
+pub mod heuristics;
+pub mod python;
+
+use comment::{Block, Comment};
+use regex;
+use std::fmt as StdFmt;
+use std::sync::Arc;
+
+use self::builtins::BUILTINS;
+
+/// A declarative description of a language, from the built-in table or a definitions file
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct LanguageDef {
+    /// The name displayed in the results
+    pub name: &'static str,
+    /// The extensions of files in this language, without the `.`
+    pub exts: &'static [&'static str],
+    /// The exact names of files in this language, such as `Makefile`
+    pub filenames: &'static [&'static str],
+    /// Names of interpreters in a `#!` line, without any version suffix (i.e. `python`)
+    pub interpreters: &'static [&'static str],
+    /// The markers starting a comment which runs to the end of the line
+    pub single: &'static [&'static str],
+    /// The delimiters of block comments
+    pub multi: &'static [Block<&'static str>],
+    /// The delimiters of string literals, inside which comment markers are ignored
+    pub strings: &'static [&'static str],
+    /// The keyword which opens an `unsafe` block, whose lines are all counted as unsafe
+    pub unsafe_keyword: Option<&'static str>,
+    /// Regular expressions matching single lines of risky code
+    pub unsafe_patterns: &'static [&'static str],
+}
+
+/// A language read from a definitions file, owned by the `LanguageTable` which loaded it and
+/// shared with every `Language::Custom` it detects
+#[allow(missing_docs)]
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct CustomDef {
+    // Each field is that of the same name in `LanguageDef`
+    pub name: String,
+    pub exts: Vec<String>,
+    pub filenames: Vec<String>,
+    pub interpreters: Vec<String>,
+    pub single: Vec<String>,
+    pub multi: Vec<Block<String>>,
+    pub strings: Vec<String>,
+    pub unsafe_keyword: Option<String>,
+    pub unsafe_patterns: Vec<String>,
+}
+
+/// A language whose source can be counted, either built in or user-defined
+// Each built-in variant is named after its language
+#[allow(missing_docs)]
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Language {
+    C,
+    Header,
+    Hpp,
+    Cpp,
+    Css,
+    Html,
+    Java,
+    JavaScript,
+    Perl,
+    Php,
+    PowerShell,
+    Python,
+    Ruby,
+    Rust,
+    Xml,
+    Toml,
+    Go,
+    Assembly,
+    Shell,
+    D,
+    Nim,
+    TypeScript,
+    Jsx,
+    Tsx,
+    Vue,
+    Svelte,
+    Scss,
+    Sass,
+    Less,
+    ObjectiveC,
+    ObjectiveCpp,
+    CSharp,
+    FSharp,
+    Kotlin,
+    Scala,
+    Groovy,
+    Swift,
+    Dart,
+    Zig,
+    Haskell,
+    OCaml,
+    Elixir,
+    Erlang,
+    Clojure,
+    Lisp,
+    Lua,
+    R,
+    Julia,
+    Pascal,
+    Prolog,
+    Sql,
+    Protobuf,
+    GraphQl,
+    Json,
+    Yaml,
+    Ini,
+    Hcl,
+    Nix,
+    Markdown,
+    Dockerfile,
+    Makefile,
+    CMake,
+    Batch,
+    Starlark,
+    Custom(Arc<CustomDef>),
+}
+
+impl Language {
+    /// The language of files with the extension `ext`, retrying in lowercase if there's no exact
+    /// match, i.e. for `.CPP`
+    pub fn from_ext(ext: &str) -> Option<Language> {
+        BUILTINS.iter()
+            .find(|&&(_, ref def)| def.exts.contains(&ext))
+            .or_else(|| {
+                let lower = ext.to_lowercase();
+                BUILTINS.iter().find(|&&(_, ref def)| def.exts.contains(&&*lower))
+            })
+            .map(|&(ref lang, _)| lang.clone())
+    }
+
+    /// The language of files named exactly `name`, such as `Makefile`
+    pub fn from_filename(name: &str) -> Option<Language> {
+        BUILTINS.iter()
+            .find(|&&(_, ref def)| def.filenames.contains(&name))
+            .map(|&(ref lang, _)| lang.clone())
+    }
+
+    /// The language of scripts run by `interpreter`, as named in a `#!` line
+    pub fn from_interpreter(interpreter: &str) -> Option<Language> {
+        BUILTINS.iter()
+            .find(|&&(_, ref def)| def.interpreters.contains(&interpreter))
+            .map(|&(ref lang, _)| lang.clone())
+    }
+
+    /// The language called `name`, ignoring case, i.e. `rust` or `C++`
+    pub fn from_name(name: &str) -> Option<Language> {
+        BUILTINS.iter()
+            .find(|&&(_, ref def)| def.name.eq_ignore_ascii_case(name))
+            .map(|&(ref lang, _)| lang.clone())
+    }
+
+    // The definition of a built-in language
+    fn builtin(&self) -> &'static LanguageDef {
+        BUILTINS.iter()
+            .find(|&&(ref l, _)| l == self)
+            .map(|&(_, ref def)| def)
+            .expect("every built-in language has an entry in the table")
+    }
+
+    /// The name displayed in the results, i.e. `C++`
+    pub fn name(&self) -> &str {
+        match *self {
+            Language::Custom(ref def) => &def.name,
+            _ => self.builtin().name,
+        }
+    }
+
+    /// Whether unsafe lines of this language can be counted, by a keyword or patterns
+    pub fn is_unsafe(&self) -> bool {
+        self.unsafe_keyword().is_some() || self.unsafe_patterns().is_some()
+    }
+
+    /// The keyword which opens an `unsafe` block, whose lines are all counted as unsafe
+    pub fn unsafe_keyword(&self) -> Option<&str> {
+        match *self {
+            Language::Custom(ref def) => def.unsafe_keyword.as_ref().map(|kw| &**kw),
+            _ => self.builtin().unsafe_keyword,
+        }
+    }
+
+    /// Regular expressions matching single lines of risky code (raw memory access, unchecked
+    /// casts, inline assembly, etc.) for languages without an explicit `unsafe` keyword
+    pub fn unsafe_patterns(&self) -> Option<Vec<&str>> {
+        let pats: Vec<&str> = match *self {
+            Language::Custom(ref def) => def.unsafe_patterns.iter().map(|p| &**p).collect(),
+            _ => self.builtin().unsafe_patterns.to_vec(),
+        };
+        if pats.is_empty() { None } else { Some(pats) }
+    }
+
+    /// The delimiters of string literals, inside which comment markers are ignored
+    pub fn strings(&self) -> Vec<String> {
+        match *self {
+            Language::Custom(ref def) => def.strings.clone(),
+            _ => self.builtin().strings.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+}
+
+/// The regex capturing the text before and after the first use of the unsafe keyword `kw`, which
+/// is matched literally and as a whole word where it starts or ends with a word character
+pub fn unsafe_keyword_regex(kw: &str) -> String {
+    format!(r"(.*?){}(.*)", keyword_pattern(kw))
+}
+
+/// The regex matching each use of the unsafe keyword `kw` which opens a block, i.e. `unsafe {`
+pub fn unsafe_block_regex(kw: &str) -> String {
+    format!(r"{}\s*\{{", keyword_pattern(kw))
+}
+
+fn keyword_pattern(kw: &str) -> String {
+    let is_word = |c: Option<char>| c.map_or(false, |c| c.is_alphanumeric() || c == '_');
+    format!("{}{}{}",
+            if is_word(kw.chars().next()) { r"\b" } else { "" },
+            regex::quote(kw),
+            if is_word(kw.chars().last()) { r"\b" } else { "" })
+}
+
+impl StdFmt::Display for Language {
+    fn fmt(&self, f: &mut StdFmt::Formatter) -> StdFmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl Comment for LanguageDef {
+    type Rep = &'static str;
+
+    fn single(&self) -> Option<Vec<<Self as Comment>::Rep>> {
+        if self.single.is_empty() {
+            None
+        } else {
+            Some(self.single.to_vec())
+        }
+    }
+
+    fn multi(&self) -> Vec<Block<<Self as Comment>::Rep>> {
+        self.multi.to_vec()
+    }
+}
+
+impl Comment for CustomDef {
+    type Rep = String;
+
+    fn single(&self) -> Option<Vec<<Self as Comment>::Rep>> {
+        if self.single.is_empty() {
+            None
+        } else {
+            Some(self.single.clone())
+        }
+    }
+
+    fn multi(&self) -> Vec<Block<<Self as Comment>::Rep>> {
+        self.multi.clone()
+    }
+}
+
+// Owned delimiters, as custom languages don't live for the whole program
+impl Comment for Language {
+    type Rep = String;
+
+    fn single(&self) -> Option<Vec<<Self as Comment>::Rep>> {
+        match *self {
+            Language::Custom(ref def) => def.single(),
+            _ => self.builtin().single().map(|v| v.iter().map(|s| s.to_string()).collect()),
+        }
+    }
+
+    fn multi(&self) -> Vec<Block<<Self as Comment>::Rep>> {
+        match *self {
+            Language::Custom(ref def) => def.multi(),
+            _ => {
+                self.builtin()
+                    .multi
+                    .iter()
+                    .map(|b| {
+                        Block {
+                            start: b.start.to_owned(),
+                            end: b.end.to_owned(),
+                            line_start: b.line_start,
+                        }
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::builtins::BUILTINS;
+    use super::Language;
+    use std::collections::HashSet;
+
+    // Lists the built-in variants, failing to compile if any is left out
+    macro_rules! variants {
+        ($($lang:ident),*) => {{
+            fn exhaustive(lang: &Language) {
+                match *lang {
+                    $(Language::$lang)|* | Language::Custom(..) => (),
+                }
+            }
+            let all = vec![$(Language::$lang),*];
+            for lang in &all {
+                exhaustive(lang);
+            }
+            all
+        }};
+    }
+
+    #[test]
+    fn every_language_has_an_entry() {
+        let all = variants![C, Header, Hpp, Cpp, Css, Html, Java, JavaScript, Perl, Php,
+                            PowerShell, Python, Ruby, Rust, Xml, Toml, Go, Assembly, Shell, D, Nim,
+                            TypeScript, Jsx, Tsx, Vue, Svelte, Scss, Sass, Less, ObjectiveC,
+                            ObjectiveCpp, CSharp, FSharp, Kotlin, Scala, Groovy, Swift, Dart, Zig,
+                            Haskell, OCaml, Elixir, Erlang, Clojure, Lisp, Lua, R, Julia, Pascal,
+                            Prolog, Sql, Protobuf, GraphQl, Json, Yaml, Ini, Hcl, Nix, Markdown,
+                            Dockerfile, Makefile, CMake, Batch, Starlark];
+        for lang in &all {
+            assert!(BUILTINS.iter().any(|&(ref l, _)| l == lang), "{:?} has no entry", lang);
+        }
+        assert_eq!(BUILTINS.len(), all.len());
+    }
+
+    #[test]
+    fn no_duplicates() {
+        let mut names = HashSet::new();
+        let mut exts = HashSet::new();
+        let mut filenames = HashSet::new();
+        let mut interpreters = HashSet::new();
+        for &(_, ref def) in BUILTINS {
+            assert!(names.insert(def.name.to_lowercase()), "duplicate name {}", def.name);
+            for ext in def.exts {
+                assert!(exts.insert(ext), "duplicate extension {}", ext);
+            }
+            for name in def.filenames {
+                assert!(filenames.insert(name), "duplicate filename {}", name);
+            }
+            for interpreter in def.interpreters {
+                assert!(interpreters.insert(interpreter),
+                        "duplicate interpreter {}",
+                        interpreter);
+            }
+        }
+    }
+}
@@ -0,0 +1,169 @@
+use count::line::{blank_literals, find_code};
+use language::Language;
+
+/// How a line relates to the triple-quoted strings of a Python file
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Line {
+    /// Part of a docstring, the string literal which is the first statement of a module, class
+    /// or function, and is counted as a comment
+    Docstring,
+    /// A continuation of an ordinary multi-line string literal, and so code
+    String,
+}
+
+/// Tracks triple-quoted strings across the lines of a Python file
+///
+/// Triple quotes aren't comments in Python, they delimit string literals which may span several
+/// lines. Only docstrings are treated as comments.
+#[derive(Debug)]
+pub struct Strings {
+    // The closing quotes of the string continuing onto the next line, and if it's a docstring
+    open: Option<(&'static str, bool)>,
+    // Whether the next statement is the first of a module, class or function
+    expect_doc: bool,
+    // The bracket depth of an unfinished `def` or `class` header, i.e. a multi-line signature
+    header: Option<i64>,
+    // Python's string delimiters, so that brackets and `#` inside strings in headers are ignored
+    strings: Vec<String>,
+}
+
+impl Strings {
+    pub fn new() -> Self {
+        Strings {
+            open: None,
+            expect_doc: true,
+            header: None,
+            strings: Language::Python.strings(),
+        }
+    }
+
+    /// The closing quotes of the string left open by the last line, and if it's a docstring
+    pub fn open(&self) -> Option<(&'static str, bool)> {
+        self.open
+    }
+
+    /// Classifies the trimmed `line`, returning `None` if it isn't inside a docstring or
+    /// continuing a string, and should be counted as usual
+    pub fn classify(&mut self, line: &str) -> Option<Line> {
+        if let Some((quotes, is_doc)) = self.open {
+            let kind = if is_doc { Line::Docstring } else { Line::String };
+            if let Some(end) = find_unescaped(line, quotes) {
+                self.open = None;
+                self.expect_doc = false;
+                // Anything following the closing quotes may open another string
+                self.scan(&line[end + 3..], false);
+            }
+            return Some(kind);
+        }
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let is_doc = self.expect_doc && starts_with_string(line);
+        self.expect_doc = false;
+        self.scan(line, is_doc);
+        self.update_header(line);
+        if is_doc {
+            Some(Line::Docstring)
+        } else {
+            None
+        }
+    }
+
+    // Skips over the string literals of `line`, recording any triple-quoted one left open
+    fn scan(&mut self, line: &str, is_doc: bool) {
+        let mut rest = line;
+        while let Some(pos) = rest.find(|c| c == '#' || c == '"' || c == '\'') {
+            if rest[pos..].starts_with('#') {
+                return;
+            }
+            let quotes = if rest[pos..].starts_with("\"\"\"") {
+                "\"\"\""
+            } else if rest[pos..].starts_with("'''") {
+                "'''"
+            } else if rest[pos..].starts_with('"') {
+                "\""
+            } else {
+                "'"
+            };
+            let body = &rest[pos + quotes.len()..];
+            match find_unescaped(body, quotes) {
+                Some(end) => rest = &body[end + quotes.len()..],
+                None => {
+                    if quotes.len() == 3 {
+                        debugln!("found open string; quotes={:?}; is_doc={:?}", quotes, is_doc);
+                        self.open = Some((quotes, is_doc));
+                    }
+                    return;
+                }
+            }
+        }
+    }
+
+    fn update_header(&mut self, line: &str) {
+        let end = find_code(line, "#", &self.strings).unwrap_or(line.len());
+        let code = blank_literals(&line[..end], &self.strings);
+        let code = code.trim();
+        let depth = match self.header {
+            Some(depth) => depth,
+            None if is_header(code) => 0,
+            None => return,
+        };
+        let depth = depth + code.matches(|c| c == '(' || c == '[').count() as i64 -
+                    code.matches(|c| c == ')' || c == ']').count() as i64;
+        if depth > 0 {
+            self.header = Some(depth);
+        } else {
+            self.header = None;
+            self.expect_doc = code.ends_with(':');
+        }
+    }
+}
+
+// `def`, `async def` and `class` statements, which may be followed by a docstring
+fn is_header(code: &str) -> bool {
+    let code = if code.starts_with("async ") { code[6..].trim_left() } else { code };
+    code.starts_with("def ") || code.starts_with("class ")
+}
+
+// Whether `line` begins with a triple-quoted string, with any prefix such as `r` or `u`
+fn starts_with_string(line: &str) -> bool {
+    let body = line.trim_left_matches(|c| "rRuUbBfF".contains(c));
+    line.len() - body.len() <= 2 && (body.starts_with("\"\"\"") || body.starts_with("'''"))
+}
+
+// The position of `quotes` in `s`, skipping any escaped with a backslash
+fn find_unescaped(s: &str, quotes: &str) -> Option<usize> {
+    let mut start = 0;
+    while let Some(pos) = s[start..].find(quotes) {
+        let pos = start + pos;
+        let escapes = s[..pos].chars().rev().take_while(|&c| c == '\\').count();
+        if escapes % 2 == 0 {
+            return Some(pos);
+        }
+        start = pos + 1;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Line, Strings};
+
+    fn classify(src: &str) -> Vec<Option<Line>> {
+        let mut strings = Strings::new();
+        src.lines().map(|line| strings.classify(line.trim())).collect()
+    }
+
+    #[test]
+    fn header_strings() {
+        let src = "def f(x=\"#(\"):\n    \"\"\"Doc.\n    \"\"\"\ndef g(y=')',\n      z=2):\n    \
+                   '''Doc.'''";
+        assert_eq!(classify(src),
+                   vec![None,
+                        Some(Line::Docstring),
+                        Some(Line::Docstring),
+                        None,
+                        None,
+                        Some(Line::Docstring)]);
+    }
+}
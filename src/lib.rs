@@ -0,0 +1,146 @@
+//! The engine behind the `cargo count` subcommand, for counting the lines of code, comments and
+//! blanks of source files, along with naive statistics on how much of them is `unsafe`.
+//!
+//! Files are counted by building a `Config` with a `ConfigBuilder`, and passing it to
+//! `count_paths`:
+//!
+//! ```no_run
+//! extern crate cargo_count;
+//!
+//! use cargo_count::ConfigBuilder;
+//!
+//! fn main() {
+//!     let cfg = ConfigBuilder::new().usafe(true).build().unwrap();
+//!     let counts = cargo_count::count_paths(&cfg).unwrap();
+//!     for count in counts.counts() {
+//!         println!("{}: {} lines of code, {} unsafe", count.lang, count.code, count.usafe);
+//!     }
+//! }
+//! ```
+//!
+//! Source which isn't in a file can be counted with `count_str`, `count_bytes` or
+//! `count_reader`, which also classify each line:
+//!
+//! ```
+//! extern crate cargo_count;
+//!
+//! use cargo_count::{Language, LineKind};
+//!
+//! fn main() {
+//!     let src = "// Says hi\nfn main() {}\n";
+//!     let source = cargo_count::count_str(Language::Rust, src);
+//!     assert_eq!((source.count().comments, source.count().code), (1, 1));
+//!     assert_eq!(source.classified()[0].kind, LineKind::Comment);
+//! }
+//! ```
+//!
+//! Each built-in language is described by a `LanguageDef`, whose comment syntax is available
+//! through the `Comment` trait. Languages besides the built-in ones can be loaded from definition
+//! files with `LanguageTable`, which owns them as `CustomDef`s.
+#![cfg_attr(feature = "nightly", feature(plugin))]
+#![cfg_attr(feature = "lints", plugin(clippy))]
+#![cfg_attr(feature = "lints", allow(explicit_iter_loop))]
+#![cfg_attr(feature = "lints", allow(should_implement_trait))]
+#![cfg_attr(feature = "lints", allow(unstable_features))]
+#![cfg_attr(feature = "lints", deny(warnings))]
+#![cfg_attr(not(any(feature = "nightly", feature = "unstable")), deny(unstable_features))]
+#![deny(missing_docs,
+        missing_debug_implementations,
+        missing_copy_implementations,
+        trivial_casts, trivial_numeric_casts,
+        unsafe_code,
+        unused_import_braces,
+        unused_qualifications)]
+
+#[cfg(feature = "color")]
+extern crate ansi_term;
+extern crate gitignore;
+extern crate glob;
+extern crate regex;
+extern crate tabwriter;
+extern crate toml;
+
+#[macro_use]
+mod macros;
+mod comment;
+mod config;
+mod count;
+mod deps;
+mod error;
+mod fmt;
+mod fsutil;
+mod langdefs;
+mod language;
+
+pub use comment::{Block, Comment};
+pub use config::{Config, ConfigBuilder, GroupBy, Metric, OutputFormat, SortBy, Utf8Rule};
+pub use count::{Count, Counts, Dir, FileCount, Group, Line, LineKind, Metrics, SourceCount,
+                UnsafePolicy};
+pub use error::{CliError, CliResult};
+pub use langdefs::LanguageTable;
+pub use language::{CustomDef, Language, LanguageDef};
+
+use fmt::Format;
+use std::io::Read;
+
+/// Counts the files found at each of `cfg.to_count`, grouped by language
+pub fn count_paths<'c>(cfg: &'c Config<'c>) -> CliResult<Counts<'c>> {
+    let mut counts = Counts::new(cfg);
+    try!(counts.fill_from());
+    try!(counts.count());
+    Ok(counts)
+}
+
+/// Counts `src`, the source of a file in `lang`, and classifies each of its lines. Any code
+/// embedded in it in another language, such as the `<script>` blocks of HTML, is counted in
+/// `Count::embedded`.
+pub fn count_str(lang: Language, src: &str) -> SourceCount {
+    let cfg = Config { usafe: true, ..Config::default() };
+    Counts::count_source(&cfg, lang, src)
+}
+
+/// Counts `src` like `count_str`, replacing any invalid UTF-8 with U+FFFD
+pub fn count_bytes(lang: Language, src: &[u8]) -> SourceCount {
+    count_str(lang, &String::from_utf8_lossy(src))
+}
+
+/// Reads all of `r` and counts it like `count_bytes`
+pub fn count_reader<R: Read>(lang: Language, mut r: R) -> CliResult<SourceCount> {
+    let mut buf = vec![];
+    cli_try!(r.read_to_end(&mut buf));
+    Ok(count_bytes(lang, &buf))
+}
+
+/// Counts and displays the results for `cfg`, as `cargo count` does
+pub fn run(cfg: Config) -> CliResult<()> {
+    debugln!("executing; cmd=run;");
+    verboseln!(cfg, "{}: {:?}", Format::Warning("Excluding"), cfg.exclude);
+    verbose!(cfg,
+             "{}",
+             if cfg.exts.is_some() {
+                 format!("{} including files with extension: {}\n",
+                         Format::Warning("Only"),
+                         cfg.exts
+                            .as_ref()
+                            .unwrap()
+                            .join(", "))
+             } else {
+                 "".to_owned()
+             });
+
+    debugln!("Checking for files or dirs to count from cli");
+
+    if let Some(ref file) = cfg.explain {
+        return count::explain(&cfg, file);
+    }
+    if cfg.deps {
+        return deps::count_deps(&cfg);
+    }
+
+    let mut counts = try!(count_paths(&cfg));
+    cli_try!(counts.write_results());
+    if cfg.check_safety {
+        try!(counts.check_safety());
+    }
+    Ok(())
+}
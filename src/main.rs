@@ -180,38 +180,25 @@
 
 #[macro_use]
 extern crate clap;
-#[cfg(feature = "color")]
-extern crate ansi_term;
-extern crate tabwriter;
-extern crate glob;
-extern crate regex;
-extern crate gitignore;
+extern crate cargo_count;
 
-#[cfg(feature = "debug")]
-use std::env;
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
 
-use clap::{App, AppSettings, Arg, SubCommand};
-
-use config::Config;
-use count::Counts;
-use error::{CliError, CliResult};
-use fmt::Format;
-
-#[macro_use]
-mod macros;
-mod comment;
-mod config;
-mod count;
-mod error;
-mod fmt;
-mod fsutil;
-mod language;
+use cargo_count::{CliError, CliResult, Config, ConfigBuilder, GroupBy, LanguageTable, OutputFormat,
+                  Utf8Rule};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process;
+use std::usize;
 
 static UTF8_RULES: [&'static str; 3] = ["strict", "lossy", "ignore"];
+static OUTPUT_FORMATS: [&'static str; 4] = ["text", "json", "markdown", "html"];
+static SORT_KEYS: [&'static str; 7] = ["lines", "code", "comments", "blanks", "files", "unsafe",
+                                       "name"];
+static GROUPS: [&'static str; 5] = ["language", "dir", "crate", "target", "extension"];
+static METRICS: [&'static str; 3] = ["ratio", "avg", "density"];
 
 fn main() {
-    debugln!("executing; cmd=cargo-count; args={:?}",
-             env::args().collect::<Vec<_>>());
     let m = App::new("cargo-count")
         .version(concat!("v", crate_version!()))
     // We have to lie about our binary name since this will be a third party
@@ -231,7 +218,24 @@ fn main() {
 -e, --exclude [PATH]...    'Files or directories to exclude (automatically includes \'.git\')'
 -a, --all                  'Do not ignore .gitignore'd paths'
 --unsafe-statistics        'Displays lines and percentages of \"unsafe\" code'
+--size-statistics          'Displays the bytes and characters of each language, not including line \
+                            endings, with the average and longest line lengths in characters'
+--deps                     'Counts the code and unsafe lines of each dependency listed in \
+                            Cargo.lock, using the sources in a vendor/ directory or the Cargo \
+                            registry cache (written as text or JSON). Cargo.lock doesn\'t record \
+                            which features are enabled, so optional dependencies are counted even \
+                            when unused'
+--tree                     'Displays --deps as a tree of the dependency graph, with the totals \
+                            of each crate and everything it depends on (implies --deps)'
 -l, --language [EXT]...    'Only count these languges (i.e. \'-l js py cpp\')'
+--skipped                  'Displays a summary of the files skipped because their language \
+                            wasn\'t recognized, grouped by extension (also shown with --verbose)'
+--list-skipped             'Lists every file skipped because its language wasn\'t recognized \
+                            (implies --skipped)'
+--embedded                 'Displays the code embedded in HTML, Vue, Svelte and Markdown files \
+                            (i.e. <script> blocks) as rows beneath them, instead of adding it to \
+                            the totals of its language'
+--per-file                 'Displays the totals of each file as well'
 -v, --verbose              'Print verbose output'
 -S, --follow-symlinks      'Follows symlinks and counts source files it finds [default: false]'
 [PATH]...                  'The files or directories (including children) to count (defaults to \
@@ -240,6 +244,66 @@ fn main() {
                     "-s, --separator [CHAR]   'Set the thousands separator for pretty printing'")
 		.use_delimiter(false)
                 .validator(single_char))
+            .arg(Arg::from_usage(
+                    "--check-safety           'Reports unsafe blocks not preceded by a safety \
+                     comment, and exits with an error if any are found'")
+                .conflicts_with_all(&["deps", "tree"]))
+            .arg(Arg::from_usage(
+                    "--safety-marker [MARKER] 'The text a comment must contain to justify an \
+                     unsafe block when using --check-safety'")
+                .default_value("SAFETY:")
+                .use_delimiter(false))
+            .arg(Arg::from_usage(
+                    "--files-from [FILE]      'Counts the files listed in FILE (or stdin when FILE \
+                     is -), separated by newlines or NULs, instead of searching PATHs'")
+                .conflicts_with("PATH")
+                .use_delimiter(false))
+            .arg(Arg::from_usage(
+                    "--stdin                  'Counts the source read from stdin, in the language \
+                     given by --lang'")
+                .requires("lang")
+                .conflicts_with_all(&["PATH", "files-from", "deps", "tree", "by-dir"]))
+            .arg(Arg::from_usage(
+                    "--lang [LANG]            'The language of the source read with --stdin, by \
+                     extension or name (i.e. \'rs\' or \'rust\')'")
+                .requires("stdin")
+                .use_delimiter(false))
+            .arg(Arg::from_usage(
+                    "--explain [FILE]         'Prints each line of FILE with how it\'s counted \
+                     (blank, comment, doc, code, mixed or unsafe) and the comments, strings and \
+                     unsafe blocks left open after it'")
+                .conflicts_with_all(&["PATH", "files-from", "stdin", "deps", "tree"])
+                .use_delimiter(false))
+            .arg(Arg::from_usage(
+                    "--by-dir                 'Displays the totals of each directory as a tree'")
+                .conflicts_with_all(&["deps", "tree"]))
+            .arg(Arg::from_usage(
+                    "--depth [DEPTH]          'Rolls up the directories of --by-dir deeper than \
+                     DEPTH into their ancestors (unlimited when omitted)'")
+                .requires("by-dir")
+                .validator(number))
+            .arg(Arg::from_usage(
+                    "--sort [KEY]             'Sorts the rows by KEY, the largest first or by \
+                     name alphabetically, instead of in the order they were found'")
+                .possible_values(&SORT_KEYS))
+            .arg(Arg::from_usage("--reverse                'Reverses the order of --sort'")
+                .requires("sort"))
+            .arg(Arg::from_usage(
+                    "--group-by [GROUP]       'Totals each GROUP of files in a row, instead of \
+                     each language'")
+                .default_value("language")
+                .possible_values(&GROUPS))
+            .arg(Arg::from_usage(
+                    "--metrics [METRIC]...    'Adds columns derived from the totals of each row: \
+                     the comment to code and blank ratios, the average lines per file, and the \
+                     unsafe lines per thousand lines of code (i.e. --metrics ratio,avg)'")
+                .require_delimiter(true)
+                .possible_values(&METRICS))
+            .arg(Arg::from_usage(
+                    "-o, --output-format [FORMAT] 'Sets the format of the results, where markdown \
+                     is a GitHub-flavored table and html a page with sortable tables and charts'")
+                .default_value("text")
+                .possible_values(&OUTPUT_FORMATS))
             .arg(Arg::from_usage("--utf8-rule [RULE]     'Sets the UTF-8 parsing rule'")
                 .default_value("strict")
                 .possible_values(&UTF8_RULES))
@@ -254,37 +318,79 @@ the current directory you could do '--exclude */test.rs'."))
         .get_matches();
 
     if let Some(m) = m.subcommand_matches("count") {
-        let cfg = Config::from_matches(m).unwrap_or_else(|e| e.exit());
-        println!("Gathering information...");
-        if let Err(e) = execute(cfg) {
-            e.exit();
+        let cfg = config(m).unwrap_or_else(|e| exit(&e));
+        if m.value_of("output-format") == Some("text") && !m.is_present("explain") {
+            println!("Gathering information...");
+        }
+        if let Err(e) = cargo_count::run(cfg) {
+            exit(&e);
         }
     }
 }
 
-fn execute(cfg: Config) -> CliResult<()> {
-    debugln!("executing; cmd=execute;");
-    verboseln!(cfg, "{}: {:?}", Format::Warning("Excluding"), cfg.exclude);
-    verbose!(cfg,
-             "{}",
-             if cfg.exts.is_some() {
-                 format!("{} including files with extension: {}\n",
-                         Format::Warning("Only"),
-                         cfg.exts
-                            .as_ref()
-                            .unwrap()
-                            .join(", "))
-             } else {
-                 "".to_owned()
-             });
+// The configuration given on the command line, which also loads any user-defined languages
+fn config<'a>(m: &'a ArgMatches<'a>) -> CliResult<Config<'a>> {
+    ConfigBuilder::new()
+        .langs(try!(LanguageTable::load()))
+        .verbose(m.is_present("verbose"))
+        .all(m.is_present("all"))
+        .thousands(m.value_of("separator").and_then(|s| s.chars().next()))
+        .usafe(m.is_present("unsafe-statistics"))
+        .utf8_rule(m.value_of("utf8-rule").and_then(|r| r.parse().ok()).unwrap_or(Utf8Rule::Strict))
+        .exclude(paths(m, "exclude"))
+        .to_count(paths(m, "PATH"))
+        .files_from(m.value_of("files-from"))
+        .stdin(m.value_of("lang"))
+        .explain(m.value_of("explain").map(PathBuf::from))
+        .exts(m.values_of("language").map(|v| v.collect()))
+        .follow_links(m.is_present("follow-symlinks"))
+        .sizes(m.is_present("size-statistics"))
+        .check_safety(m.is_present("check-safety"))
+        .safety_marker(m.value_of("safety-marker").unwrap_or("SAFETY:"))
+        .deps(m.is_present("deps"))
+        .deps_tree(m.is_present("tree"))
+        .output(m.value_of("output-format")
+            .and_then(|f| f.parse().ok())
+            .unwrap_or(OutputFormat::Text))
+        .by_dir(if m.is_present("by-dir") {
+            Some(m.value_of("depth").and_then(|d| d.parse().ok()).unwrap_or(usize::MAX))
+        } else {
+            None
+        })
+        .sort(m.value_of("sort").and_then(|s| s.parse().ok()))
+        .reverse(m.is_present("reverse"))
+        .group_by(m.value_of("group-by").and_then(|g| g.parse().ok()).unwrap_or(GroupBy::Language))
+        .metrics(m.values_of("metrics")
+            .map(|v| v.filter_map(|s| s.parse().ok()).collect())
+            .unwrap_or_default())
+        .skipped(m.is_present("skipped"))
+        .list_skipped(m.is_present("list-skipped"))
+        .per_file(m.is_present("per-file"))
+        .embedded(m.is_present("embedded"))
+        .build()
+}
+
+fn paths(m: &ArgMatches, name: &str) -> Vec<PathBuf> {
+    m.values_of(name).map(|v| v.map(PathBuf::from).collect()).unwrap_or_default()
+}
 
-    debugln!("Checking for files or dirs to count from cli");
+// Prints `e` and exits, with a failure status if it's fatal
+fn exit(e: &CliError) -> ! {
+    if e.is_fatal() {
+        writeln!(&mut io::stderr(), "{}", e).ok();
+        process::exit(1)
+    } else {
+        println!("{}", e);
+        process::exit(0)
+    }
+}
 
-    let mut counts = Counts::new(&cfg);
-    counts.fill_from();
-    cli_try!(counts.count());
-    cli_try!(counts.write_results());
-    Ok(())
+fn number(s: String) -> Result<(), String> {
+    if s.parse::<usize>().is_ok() {
+        Ok(())
+    } else {
+        Err(format!("the --depth argument option only accepts a number but found '{}'", s))
+    }
 }
 
 fn single_char(s: String) -> Result<(), String> {
@@ -294,6 +400,6 @@ fn single_char(s: String) -> Result<(), String> {
         Err(
           format!(
             "the --separator argument option only accepts a single character but found '{}'",
-             Format::Warning(s)))
+             s))
     }
 }